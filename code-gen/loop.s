@@ -0,0 +1,22 @@
+; 1から10までの和と、そのうちの奇数の和
+loadi %1, 0     ; i
+loadi %2, 10
+loadi %3, 1
+loadi %4, 0     ; sum
+loadi %5, 0     ; odd sum
+loadi %6, 1     ; 次のiが奇数なら1
+
+loop:
+add %1, %1, %3
+add %4, %4, %1
+bne %6, %3, even
+add %5, %5, %1
+loadi %6, 0
+jmp next
+even:
+loadi %6, 1
+next:
+blt %1, %2, loop
+
+print %4 ; => 55
+print %5 ; => 25
@@ -0,0 +1,251 @@
+use std::collections::{BTreeSet, HashMap};
+
+use alloc::{check_program, spill_base, virtual_registers, AllocConfig, AllocError, Allocation, RegisterAllocator};
+use alloc::rewrite::{apply_colors, insert_spill_code};
+use alloc::spill_cost::{occurrences, Candidate, Frequency, SpillDecision, SpillHeuristic, SpillQueue, UsesPerDegree};
+use ir::{max_register_id, OpeCode};
+use bitset::BitSet;
+use interference::InterferenceGraph;
+use renumber::Renumbering;
+use webs::split_webs;
+
+// 一時レジスタを取っておかないので、spillした2つのレジスタを読む命令の一時レジスタの分だけあればよい
+const MIN_COLORING_REGISTER_NUM: usize = 2;
+
+/// Chatinのアルゴリズム(干渉グラフを用いる)
+pub fn allocate_registers2(opcodes: Vec<OpeCode>, max_register_num: usize) -> Result<Vec<OpeCode>, AllocError> {
+    color_graph(opcodes, max_register_num, Coloring::Pessimistic, &Frequency::LoopDepth, &UsesPerDegree).map(|colored| colored.code)
+}
+
+/// 次数の小さいノードがなくなったときの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coloring {
+    /// その場でspillする (Chaitin)
+    Pessimistic,
+    /// spillの候補としてスタックに積み、塗るときに色が残っていなければspillする (Briggs)
+    Optimistic,
+}
+
+/// Chatinのアルゴリズムによる割り当て
+///
+/// spillするレジスタはループの深さで重み付けした読み書きの回数 / 次数が最も小さいもの。
+/// spillしたら読み書きの前後に新しい一時レジスタでLoad, Storeを入れ、塗れるまで干渉グラフを作り直す。
+/// 一時レジスタも他と同じように塗るので、spillのために取っておくレジスタはない
+pub struct Chaitin {
+    config: AllocConfig,
+    coloring: Coloring,
+    frequency: Frequency,
+    heuristic: Box<dyn SpillHeuristic>,
+}
+
+impl Chaitin {
+    pub fn new(config: AllocConfig) -> Chaitin {
+        Chaitin::with_coloring(config, Coloring::Pessimistic)
+    }
+
+    pub fn with_coloring(config: AllocConfig, coloring: Coloring) -> Chaitin {
+        Chaitin { config, coloring, frequency: Frequency::LoopDepth, heuristic: Box::new(UsesPerDegree) }
+    }
+
+    /// 命令の実行回数の見積もり方を変える
+    pub fn with_frequency(mut self, frequency: Frequency) -> Chaitin {
+        self.frequency = frequency;
+        self
+    }
+
+    /// spillするレジスタの選び方を変える
+    pub fn with_spill_heuristic(mut self, heuristic: Box<dyn SpillHeuristic>) -> Chaitin {
+        self.heuristic = heuristic;
+        self
+    }
+}
+
+impl RegisterAllocator for Chaitin {
+    fn name(&self) -> &'static str {
+        match self.coloring {
+            Coloring::Pessimistic => "chaitin",
+            Coloring::Optimistic => "briggs",
+        }
+    }
+
+    fn config(&self) -> &AllocConfig {
+        &self.config
+    }
+
+    fn min_register_num(&self) -> usize {
+        MIN_COLORING_REGISTER_NUM
+    }
+
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError> {
+        let Colored { code, ranges, reg_map, spilled_reg, reg_addr_map, decisions } =
+            color_graph(opcodes.to_vec(), self.config.register_num, self.coloring, &self.frequency, &*self.heuristic)?;
+        let spilled: BTreeSet<usize> = spilled_reg.into_iter().collect();
+        let assignment = virtual_registers(&ranges).into_iter()
+            .filter(|reg_id| !spilled.contains(reg_id))
+            .filter_map(|reg_id| reg_map.get(&reg_id).map(|&color| (reg_id, color)))
+            .collect();
+
+        let slots = reg_addr_map.into_iter().map(|(reg_id, addr)| (reg_id, addr as i32)).collect();
+
+        Ok(Allocation::new(opcodes, code, assignment, spilled, slots).with_ranges(ranges).with_spill_decisions(decisions))
+    }
+}
+
+// 割り当て後のコードとwebに分けたプログラム、webの番号 -> 色, spillしたweb, webの番号 -> アドレス, spillの候補の選択
+// (webの番号は元のプログラムの番号に合わせたもの)
+struct Colored {
+    code: Vec<OpeCode>,
+    ranges: Vec<OpeCode>,
+    reg_map: HashMap<usize, usize>,
+    spilled_reg: Vec<usize>,
+    reg_addr_map: HashMap<usize, usize>,
+    decisions: Vec<SpillDecision>,
+}
+
+fn color_graph(opcodes: Vec<OpeCode>, max_register_num: usize, coloring: Coloring, frequency: &Frequency, heuristic: &dyn SpillHeuristic) -> Result<Colored, AllocError> {
+    check_program(&opcodes, max_register_num, MIN_COLORING_REGISTER_NUM)?;
+
+    // 0から詰めた番号に付け直してからwebに分ける。以降のレジスタ番号はwebの番号
+    let renumbering = Renumbering::new(&opcodes);
+    let ranges = split_webs(&renumbering.apply(&opcodes)).code;
+    // これ以上の番号はspillのために作った一時レジスタ
+    let web_num = max_register_id(&ranges) + 1;
+    let frequencies = frequency.estimate(&ranges);
+
+    // spillのコードを入れたプログラムと、その各命令がrangesの何番目の命令から作られたか
+    let mut code = ranges.clone();
+    let mut origins: Vec<usize> = (0..code.len()).collect();
+    let mut next_id = web_num;
+    // spillしたweb -> アドレス
+    let mut reg_addr_map: HashMap<usize, usize> = HashMap::new();
+    let mut decisions: Vec<SpillDecision> = Vec::new();
+
+    // すべてのレジスタを塗るのに使う
+    let threshold = max_register_num;
+
+    let reg_map = loop {
+        let weights: Vec<f64> = origins.iter().map(|&index| frequencies[index]).collect();
+        let register_num = max_register_id(&code) + 1;
+        // レジスタごとの (読む回数, 書き込む回数, 重み付けした読み書きの回数)
+        let occurrences = occurrences(&code, &weights, register_num);
+        let mut graph = InterferenceGraph::build(&code, &BitSet::new(register_num));
+
+        // 次数がthreshold未満のノードと、それ以外のノード
+        let (mut low, mut high): (BTreeSet<usize>, BTreeSet<usize>) = (0..register_num).partition(|&reg_id| graph.degree(reg_id) < threshold);
+
+        // 一時レジスタはspillしても生存区間が短くならないので候補にしない
+        let candidate = |graph: &InterferenceGraph, reg_id: usize| {
+            let (uses, defs, weight) = occurrences[reg_id];
+            Candidate { reg: reg_id, uses, defs, weight, degree: graph.degree(reg_id) }
+        };
+        let mut queue = SpillQueue::new(heuristic);
+        for &reg_id in high.iter().filter(|&&reg_id| reg_id < web_num) {
+            queue.push(&candidate(&graph, reg_id));
+        }
+        // highに残っている候補の数
+        let mut candidates = high.range(..web_num).count();
+
+        let mut spill_list: Vec<usize> = Vec::new();
+        let mut removed_regs: Vec<usize> = Vec::with_capacity(register_num);
+
+        while removed_regs.len() < register_num {
+            let reg_id = match low.iter().next().cloned() {
+                Some(reg_id) => {
+                    low.remove(&reg_id);
+                    reg_id
+                },
+                None => {
+                    let (candidate, cost) = queue.pop(|reg_id| if high.contains(&reg_id) { Some(candidate(&graph, reg_id)) } else { None })
+                        .ok_or(AllocError::Uncolorable)?;
+                    let reg_id = candidate.reg;
+                    decisions.push(SpillDecision { candidate, cost, candidates, spilled: true });
+                    high.remove(&reg_id);
+                    candidates -= 1;
+                    if coloring == Coloring::Pessimistic {
+                        spill_list.push(reg_id);
+                    }
+                    reg_id
+                },
+            };
+            // 干渉グラフからreg_idを取り除く
+            for neighbor in graph.remove(reg_id) {
+                if graph.degree(neighbor) + 1 == threshold && high.remove(&neighbor) {
+                    low.insert(neighbor);
+                    if neighbor < web_num {
+                        candidates -= 1;
+                    }
+                } else if neighbor < web_num && high.contains(&neighbor) {
+                    queue.push(&candidate(&graph, neighbor));
+                }
+            }
+            removed_regs.push(reg_id);
+        }
+
+        let mut reg_map: HashMap<usize, usize> = HashMap::new();
+        if spill_list.is_empty() {
+            // 塗る
+            let mut is_painted: Vec<bool> = vec![false; max_register_num + 1];
+            for &reg_id in removed_regs.iter().rev() {
+                for &reg_id2 in graph.neighbors(reg_id) {
+                    if let Some(&color) = reg_map.get(&reg_id2) {
+                        is_painted[color] = true;
+                    }
+                }
+
+                let color = (1..threshold + 1).find(|&color| !is_painted[color]);
+
+                for &reg_id2 in graph.neighbors(reg_id) {
+                    if let Some(&color) = reg_map.get(&reg_id2) {
+                        is_painted[color] = false;
+                    }
+                }
+
+                match color {
+                    Some(color) => {
+                        reg_map.insert(reg_id, color);
+                    },
+                    None if coloring == Coloring::Optimistic => spill_list.push(reg_id),
+                    None => return Err(AllocError::Uncolorable),
+                }
+            }
+
+            if spill_list.is_empty() {
+                break reg_map;
+            }
+        }
+
+        // spillするwebを読み書きする命令の前後にLoad, Storeを入れ、干渉グラフを作り直す
+        let spill_base = spill_base(&ranges, reg_addr_map.len() + spill_list.len())?;
+        let mut slots: HashMap<usize, usize> = HashMap::new();
+        for &reg_id in &spill_list {
+            let addr = spill_base + reg_addr_map.len();
+            reg_addr_map.insert(reg_id, addr);
+            slots.insert(reg_id, addr);
+        }
+        let (spilled_code, spilled_origins) = insert_spill_code(&code, &slots, &mut next_id);
+        origins = spilled_origins.into_iter().map(|index| origins[index]).collect();
+        code = spilled_code;
+    };
+
+    for decision in &mut decisions {
+        // 楽観的な彩色で塗れたもの
+        decision.spilled = reg_addr_map.contains_key(&decision.candidate.reg);
+        decision.candidate.reg = renumbering.sparse(decision.candidate.reg);
+    }
+
+    let result = apply_colors(&code, &reg_map)?;
+
+    // 元の番号に戻す (一時レジスタは元のプログラムにないので除く)
+    let sparse = |map: HashMap<usize, usize>| map.into_iter()
+        .filter(|&(reg_id, _)| reg_id < web_num)
+        .map(|(reg_id, value)| (renumbering.sparse(reg_id), value))
+        .collect();
+    Ok(Colored {
+        code: result,
+        ranges: renumbering.restore(&ranges),
+        reg_map: sparse(reg_map),
+        spilled_reg: reg_addr_map.keys().map(|&reg_id| renumbering.sparse(reg_id)).collect(),
+        reg_addr_map: sparse(reg_addr_map),
+        decisions,
+    })
+}
@@ -0,0 +1,460 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use alloc::{check_program, virtual_registers, AllocConfig, AllocError, Allocation, RegisterAllocator, MIN_REGISTER_NUM};
+use alloc::rewrite::rewrite;
+use alloc::spill_cost::{occurrences, Candidate, Frequency, SpillHeuristic, SpillQueue, UsesPerDegree};
+use bitset::BitSet;
+use interference::InterferenceGraph;
+use ir::{max_register_id, OpeCode};
+use renumber::Renumbering;
+use webs::split_webs;
+
+/// George, Appelの反復レジスタ合併 (iterated register coalescing)
+///
+/// Movの両端を同じ色に塗れるときは1つのノードにまとめ、同じレジスタへのコピーを消す
+pub fn allocate_registers_coalescing(opcodes: Vec<OpeCode>, max_register_num: usize) -> Result<Vec<OpeCode>, AllocError> {
+    coalesce(&opcodes, max_register_num, &Frequency::LoopDepth, &UsesPerDegree).map(|coalesced| coalesced.code)
+}
+
+/// 反復レジスタ合併による割り当て
+///
+/// spillの候補はChaitinと同じく`SpillHeuristic`のコストが最も小さいもの。
+/// spillしたレジスタはnaive, linear-scanと同じく2つの一時レジスタ (%(N-1), %N) で読み書きするので、
+/// 塗るのに使えるのはN-2色 (Chaitinのように一時レジスタも塗る書き換えはしていない)
+pub struct IteratedCoalescing {
+    config: AllocConfig,
+    frequency: Frequency,
+    heuristic: Box<dyn SpillHeuristic>,
+}
+
+impl IteratedCoalescing {
+    pub fn new(config: AllocConfig) -> IteratedCoalescing {
+        IteratedCoalescing { config, frequency: Frequency::LoopDepth, heuristic: Box::new(UsesPerDegree) }
+    }
+
+    /// 命令の実行回数の見積もり方を変える
+    pub fn with_frequency(mut self, frequency: Frequency) -> IteratedCoalescing {
+        self.frequency = frequency;
+        self
+    }
+
+    /// spillするレジスタの選び方を変える
+    pub fn with_spill_heuristic(mut self, heuristic: Box<dyn SpillHeuristic>) -> IteratedCoalescing {
+        self.heuristic = heuristic;
+        self
+    }
+}
+
+impl RegisterAllocator for IteratedCoalescing {
+    fn name(&self) -> &'static str {
+        "irc"
+    }
+
+    fn config(&self) -> &AllocConfig {
+        &self.config
+    }
+
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError> {
+        let Coalesced { code, ranges, reg_map, spilled_reg, reg_addr_map } = coalesce(opcodes, self.config.register_num, &self.frequency, &*self.heuristic)?;
+        let spilled: BTreeSet<usize> = spilled_reg.into_iter().collect();
+        let assignment = virtual_registers(&ranges).into_iter()
+            .filter(|reg_id| !spilled.contains(reg_id))
+            .filter_map(|reg_id| reg_map.get(&reg_id).map(|&color| (reg_id, color)))
+            .collect();
+
+        let slots = reg_addr_map.into_iter().map(|(reg_id, addr)| (reg_id, addr as i32)).collect();
+
+        Ok(Allocation::new(opcodes, code, assignment, spilled, slots).with_ranges(ranges))
+    }
+}
+
+// 割り当て後のコードとwebに分けたプログラム、webの番号 -> 色, spillしたweb, webの番号 -> アドレス
+// (webの番号は元のプログラムの番号に合わせたもの)
+struct Coalesced {
+    code: Vec<OpeCode>,
+    ranges: Vec<OpeCode>,
+    reg_map: HashMap<usize, usize>,
+    spilled_reg: Vec<usize>,
+    reg_addr_map: HashMap<usize, usize>,
+}
+
+// ノードがどの集合に入っているか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeState {
+    // spillしたweb (グラフにない)
+    Dead,
+    Simplify,
+    Freeze,
+    Spill,
+    Coalesced,
+    // 塗るためのスタックに積んだ
+    Selected,
+}
+
+// Movがどの集合に入っているか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MoveState {
+    // まだ合併を試していない
+    Worklist,
+    // 合併すると塗れなくなるかもしれないので、次数が下がるのを待つ
+    Active,
+    Coalesced,
+    // 両端が干渉している
+    Constrained,
+    // 合併をあきらめた
+    Frozen,
+}
+
+struct Coalescer<'a> {
+    graph: InterferenceGraph,
+    // 使える色の数
+    k: usize,
+    degree: Vec<usize>,
+    // ノードごとの (読む回数, 書き込む回数, 重み付けした読み書きの回数)。合併したノードは足し合わせる
+    occurrences: Vec<(usize, usize, f64)>,
+    // spill_worklistのノードをコストの小さい順に取り出す
+    spill_queue: SpillQueue<'a>,
+    state: Vec<NodeState>,
+    alias: Vec<usize>,
+    // (書き込み先, 読み込み元)
+    moves: Vec<(usize, usize)>,
+    move_state: Vec<MoveState>,
+    // ノード -> 関係するMov
+    move_list: Vec<Vec<usize>>,
+    simplify_worklist: BTreeSet<usize>,
+    freeze_worklist: BTreeSet<usize>,
+    spill_worklist: BTreeSet<usize>,
+    worklist_moves: BTreeSet<usize>,
+    select_stack: Vec<usize>,
+}
+
+// spillの候補としてのノード
+fn candidate(n: usize, degree: &[usize], occurrences: &[(usize, usize, f64)]) -> Candidate {
+    let (uses, defs, weight) = occurrences[n];
+    Candidate { reg: n, uses, defs, weight, degree: degree[n] }
+}
+
+impl<'a> Coalescer<'a> {
+    fn new(opcodes: &[OpeCode], dead: &BitSet, k: usize, frequencies: &[f64], heuristic: &'a dyn SpillHeuristic) -> Coalescer<'a> {
+        let graph = InterferenceGraph::build(opcodes, dead);
+        let len = graph.len();
+
+        let moves: Vec<(usize, usize)> = opcodes.iter()
+            .filter_map(|op| op.as_move())
+            .map(|(dst, src)| (dst.id, src.id))
+            .filter(|&(dst, src)| dst != src && !dead.contains(dst) && !dead.contains(src))
+            .collect();
+        let mut move_list = vec![Vec::new(); len];
+        for (m, &(dst, src)) in moves.iter().enumerate() {
+            move_list[dst].push(m);
+            move_list[src].push(m);
+        }
+
+        let mut coalescer = Coalescer {
+            degree: (0..len).map(|n| graph.degree(n)).collect(),
+            graph,
+            k,
+            occurrences: occurrences(opcodes, frequencies, len),
+            spill_queue: SpillQueue::new(heuristic),
+            state: vec![NodeState::Dead; len],
+            alias: (0..len).collect(),
+            move_state: vec![MoveState::Worklist; moves.len()],
+            worklist_moves: (0..moves.len()).collect(),
+            moves,
+            move_list,
+            simplify_worklist: BTreeSet::new(),
+            freeze_worklist: BTreeSet::new(),
+            spill_worklist: BTreeSet::new(),
+            select_stack: Vec::new(),
+        };
+
+        for n in (0..len).filter(|&n| !dead.contains(n)) {
+            if coalescer.degree[n] >= k {
+                coalescer.set_state(n, NodeState::Spill);
+            } else if coalescer.move_related(n) {
+                coalescer.set_state(n, NodeState::Freeze);
+            } else {
+                coalescer.set_state(n, NodeState::Simplify);
+            }
+        }
+
+        coalescer
+    }
+
+    // ノードを今の集合から外して、stateの集合に入れる
+    fn set_state(&mut self, n: usize, state: NodeState) {
+        match self.state[n] {
+            NodeState::Simplify => { self.simplify_worklist.remove(&n); },
+            NodeState::Freeze => { self.freeze_worklist.remove(&n); },
+            NodeState::Spill => { self.spill_worklist.remove(&n); },
+            _ => {},
+        }
+        match state {
+            NodeState::Simplify => { self.simplify_worklist.insert(n); },
+            NodeState::Freeze => { self.freeze_worklist.insert(n); },
+            NodeState::Spill => { self.spill_worklist.insert(n); },
+            _ => {},
+        }
+        self.state[n] = state;
+        self.requeue(n);
+    }
+
+    // spill_worklistのノードの次数か重みが変わったらコストを計算し直す
+    fn requeue(&mut self, n: usize) {
+        if self.state[n] == NodeState::Spill {
+            self.spill_queue.push(&candidate(n, &self.degree, &self.occurrences));
+        }
+    }
+
+    fn set_move_state(&mut self, m: usize, state: MoveState) {
+        if self.move_state[m] == MoveState::Worklist {
+            self.worklist_moves.remove(&m);
+        }
+        if state == MoveState::Worklist {
+            self.worklist_moves.insert(m);
+        }
+        self.move_state[m] = state;
+    }
+
+    // スタックに積んだノードと合併されたノードを除いた隣接ノード
+    fn adjacent(&self, n: usize) -> Vec<usize> {
+        self.graph.neighbors(n).iter().cloned()
+            .filter(|&m| !matches!(self.state[m], NodeState::Selected | NodeState::Coalesced))
+            .collect()
+    }
+
+    // まだ合併するかもしれないMov
+    fn node_moves(&self, n: usize) -> Vec<usize> {
+        self.move_list[n].iter().cloned()
+            .filter(|&m| matches!(self.move_state[m], MoveState::Worklist | MoveState::Active))
+            .collect()
+    }
+
+    fn move_related(&self, n: usize) -> bool {
+        !self.node_moves(n).is_empty()
+    }
+
+    fn alias(&self, n: usize) -> usize {
+        let mut n = n;
+        while self.state[n] == NodeState::Coalesced {
+            n = self.alias[n];
+        }
+        n
+    }
+
+    fn add_edge(&mut self, u: usize, v: usize) {
+        if self.graph.add_edge(u, v) {
+            self.degree[u] += 1;
+            self.degree[v] += 1;
+            self.requeue(u);
+            self.requeue(v);
+        }
+    }
+
+    fn decrement_degree(&mut self, m: usize) {
+        let d = self.degree[m];
+        self.degree[m] = d - 1;
+        self.requeue(m);
+        if d == self.k && self.state[m] == NodeState::Spill {
+            let mut nodes = self.adjacent(m);
+            nodes.push(m);
+            self.enable_moves(&nodes);
+            let state = if self.move_related(m) { NodeState::Freeze } else { NodeState::Simplify };
+            self.set_state(m, state);
+        }
+    }
+
+    fn enable_moves(&mut self, nodes: &[usize]) {
+        for &n in nodes {
+            for m in self.node_moves(n) {
+                if self.move_state[m] == MoveState::Active {
+                    self.set_move_state(m, MoveState::Worklist);
+                }
+            }
+        }
+    }
+
+    fn simplify(&mut self, n: usize) {
+        self.set_state(n, NodeState::Selected);
+        self.select_stack.push(n);
+        for m in self.adjacent(n) {
+            self.decrement_degree(m);
+        }
+    }
+
+    // 合併を試せなくなり、次数も小さいノードを単純化に回す
+    fn add_worklist(&mut self, u: usize) {
+        if self.state[u] == NodeState::Freeze && !self.move_related(u) && self.degree[u] < self.k {
+            self.set_state(u, NodeState::Simplify);
+        }
+    }
+
+    // George: vの隣接ノードはどれも次数が小さいか、すでにuと干渉している
+    fn george(&self, u: usize, v: usize) -> bool {
+        self.adjacent(v).into_iter().all(|t| self.degree[t] < self.k || self.graph.interferes(t, u))
+    }
+
+    // Briggs: 合併したノードの隣接ノードのうち、次数の大きいものがk個未満
+    fn briggs(&self, u: usize, v: usize) -> bool {
+        let mut nodes: BTreeSet<usize> = self.adjacent(u).into_iter().collect();
+        nodes.extend(self.adjacent(v));
+        nodes.into_iter().filter(|&n| self.degree[n] >= self.k).count() < self.k
+    }
+
+    fn coalesce(&mut self, m: usize) {
+        let (x, y) = self.moves[m];
+        let (u, v) = (self.alias(x), self.alias(y));
+        if u == v {
+            self.set_move_state(m, MoveState::Coalesced);
+            self.add_worklist(u);
+        } else if self.graph.interferes(u, v) {
+            self.set_move_state(m, MoveState::Constrained);
+            self.add_worklist(u);
+            self.add_worklist(v);
+        } else if self.george(u, v) || self.briggs(u, v) {
+            self.set_move_state(m, MoveState::Coalesced);
+            self.combine(u, v);
+            self.add_worklist(u);
+        } else {
+            self.set_move_state(m, MoveState::Active);
+        }
+    }
+
+    // vをuにまとめる
+    fn combine(&mut self, u: usize, v: usize) {
+        self.set_state(v, NodeState::Coalesced);
+        self.alias[v] = u;
+        let (uses, defs, weight) = self.occurrences[v];
+        self.occurrences[u].0 += uses;
+        self.occurrences[u].1 += defs;
+        self.occurrences[u].2 += weight;
+        self.requeue(u);
+        let moves = self.move_list[v].clone();
+        self.move_list[u].extend(moves);
+        self.enable_moves(&[v]);
+        for t in self.adjacent(v) {
+            self.add_edge(t, u);
+            self.decrement_degree(t);
+        }
+        if self.degree[u] >= self.k && self.state[u] == NodeState::Freeze {
+            self.set_state(u, NodeState::Spill);
+        }
+    }
+
+    fn freeze(&mut self, u: usize) {
+        self.set_state(u, NodeState::Simplify);
+        self.freeze_moves(u);
+    }
+
+    fn freeze_moves(&mut self, u: usize) {
+        for m in self.node_moves(u) {
+            let (x, y) = self.moves[m];
+            let v = if self.alias(y) == self.alias(u) { self.alias(x) } else { self.alias(y) };
+            self.set_move_state(m, MoveState::Frozen);
+            if self.state[v] == NodeState::Freeze && !self.move_related(v) && self.degree[v] < self.k {
+                self.set_state(v, NodeState::Simplify);
+            }
+        }
+    }
+
+    // コストの最も小さいノードをspillの候補にする
+    fn select_spill(&mut self) {
+        let (state, degree, occurrences) = (&self.state, &self.degree, &self.occurrences);
+        let n = self.spill_queue.pop(|n| if state[n] == NodeState::Spill { Some(candidate(n, degree, occurrences)) } else { None })
+            .map(|(candidate, _)| candidate.reg)
+            .expect("every node in spill_worklist is queued");
+        self.set_state(n, NodeState::Simplify);
+        self.freeze_moves(n);
+    }
+
+    fn run(&mut self) {
+        loop {
+            if let Some(&n) = self.simplify_worklist.iter().next() {
+                self.simplify(n);
+            } else if let Some(&m) = self.worklist_moves.iter().next() {
+                self.coalesce(m);
+            } else if let Some(&n) = self.freeze_worklist.iter().next() {
+                self.freeze(n);
+            } else if !self.spill_worklist.is_empty() {
+                self.select_spill();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // 色を塗る。塗れなかったノードを返す
+    fn assign_colors(&mut self, reg_map: &mut HashMap<usize, usize>) -> Vec<usize> {
+        let mut spilled = Vec::new();
+        while let Some(n) = self.select_stack.pop() {
+            let mut is_painted = vec![false; self.k + 1];
+            for &w in self.graph.neighbors(n) {
+                if let Some(&color) = reg_map.get(&self.alias(w)) {
+                    is_painted[color] = true;
+                }
+            }
+            match (1..self.k + 1).find(|&color| !is_painted[color]) {
+                Some(color) => {
+                    reg_map.insert(n, color);
+                },
+                None => spilled.push(n),
+            }
+        }
+
+        if spilled.is_empty() {
+            for n in 0..self.state.len() {
+                if self.state[n] == NodeState::Coalesced {
+                    let color = reg_map[&self.alias(n)];
+                    reg_map.insert(n, color);
+                }
+            }
+        }
+        spilled
+    }
+}
+
+fn coalesce(opcodes: &[OpeCode], max_register_num: usize, frequency: &Frequency, heuristic: &dyn SpillHeuristic) -> Result<Coalesced, AllocError> {
+    check_program(opcodes, max_register_num, MIN_REGISTER_NUM)?;
+
+    // 0から詰めた番号に付け直してからwebに分ける。以降のレジスタ番号はwebの番号
+    let renumbering = Renumbering::new(opcodes);
+    let opcodes = split_webs(&renumbering.apply(opcodes)).code;
+    let frequencies = frequency.estimate(&opcodes);
+    // spillして干渉グラフから外したレジスタ
+    let mut dead: BitSet = BitSet::new(max_register_id(&opcodes) + 1);
+
+    let mut reg_map: HashMap<usize, usize> = HashMap::new();
+
+    // 残り2つは一時レジスタ
+    let k = max_register_num - 2;
+
+    loop {
+        let mut coalescer = Coalescer::new(&opcodes, &dead, k, &frequencies, heuristic);
+        coalescer.run();
+
+        let spill_list = coalescer.assign_colors(&mut reg_map);
+        if spill_list.is_empty() {
+            break;
+        }
+
+        // 塗れなかったノードを外し、合併もやり直す
+        reg_map.clear();
+        for reg_id in spill_list {
+            dead.insert(reg_id);
+        }
+    }
+
+    let spilled_reg: Vec<usize> = dead.iter().collect();
+    let spilled: HashSet<usize> = spilled_reg.iter().cloned().collect();
+    let (result, reg_addr_map) = rewrite(&opcodes, &reg_map, &spilled, max_register_num)?;
+
+    // 元の番号に戻す
+    let sparse = |map: HashMap<usize, usize>| map.into_iter().map(|(reg_id, value)| (renumbering.sparse(reg_id), value)).collect();
+    Ok(Coalesced {
+        code: result,
+        ranges: renumbering.restore(&opcodes),
+        reg_map: sparse(reg_map),
+        spilled_reg: spilled_reg.into_iter().map(|reg_id| renumbering.sparse(reg_id)).collect(),
+        reg_addr_map: sparse(reg_addr_map),
+    })
+}
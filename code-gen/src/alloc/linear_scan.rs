@@ -0,0 +1,177 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use alloc::{check_program, virtual_registers, AllocConfig, AllocError, Allocation, RegisterAllocator, MIN_REGISTER_NUM};
+use alloc::rewrite::rewrite;
+use cfg::Cfg;
+use ir::{max_register_id, OpeCode};
+use liveness::block_liveness;
+use renumber::Renumbering;
+use webs::split_webs;
+
+/// Poletto, Sarkarの線形走査による割り当て
+///
+/// 生存区間を始点の順に見ていき、レジスタが足りなければ終点が最も遠い区間をspillする
+pub fn allocate_registers_linear_scan(opcodes: Vec<OpeCode>, max_register_num: usize) -> Result<Vec<OpeCode>, AllocError> {
+    scan(&opcodes, max_register_num).map(|scanned| scanned.code)
+}
+
+/// 線形走査による割り当て
+pub struct LinearScan {
+    config: AllocConfig,
+}
+
+impl LinearScan {
+    pub fn new(config: AllocConfig) -> LinearScan {
+        LinearScan { config }
+    }
+}
+
+impl RegisterAllocator for LinearScan {
+    fn name(&self) -> &'static str {
+        "linear-scan"
+    }
+
+    fn config(&self) -> &AllocConfig {
+        &self.config
+    }
+
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError> {
+        let Scanned { code, ranges, reg_map, spilled_reg, reg_addr_map } = scan(opcodes, self.config.register_num)?;
+        let spilled: BTreeSet<usize> = spilled_reg.into_iter().collect();
+        let assignment = virtual_registers(&ranges).into_iter()
+            .filter(|reg_id| !spilled.contains(reg_id))
+            .filter_map(|reg_id| reg_map.get(&reg_id).map(|&color| (reg_id, color)))
+            .collect();
+
+        let slots = reg_addr_map.into_iter().map(|(reg_id, addr)| (reg_id, addr as i32)).collect();
+
+        Ok(Allocation::new(opcodes, code, assignment, spilled, slots).with_ranges(ranges))
+    }
+}
+
+// 割り当て後のコードとwebに分けたプログラム、webの番号 -> 物理レジスタ, spillしたweb, webの番号 -> アドレス
+// (webの番号は元のプログラムの番号に合わせたもの)
+struct Scanned {
+    code: Vec<OpeCode>,
+    ranges: Vec<OpeCode>,
+    reg_map: HashMap<usize, usize>,
+    spilled_reg: Vec<usize>,
+    reg_addr_map: HashMap<usize, usize>,
+}
+
+/// 生存区間
+///
+/// 位置はpoint番目の命令で読むときが2 * point、書き込むときが2 * point + 1。
+/// 制御フローがあるときは、生きている位置をすべて含む最小の区間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Interval {
+    start: usize,
+    end: usize,
+    reg_id: usize,
+}
+
+fn live_intervals(opcodes: &[OpeCode]) -> Vec<Interval> {
+    let register_num = max_register_id(opcodes) + 1;
+    let mut bounds: Vec<Option<(usize, usize)>> = vec![None; register_num];
+    let mut extend = |reg_id: usize, position: usize| {
+        let bound = bounds[reg_id].get_or_insert((position, position));
+        bound.0 = bound.0.min(position);
+        bound.1 = bound.1.max(position);
+    };
+
+    for (point, opcode) in opcodes.iter().enumerate() {
+        for src in opcode.srcs() {
+            extend(src.id, 2 * point);
+        }
+        if let Some(dst) = opcode.dst() {
+            extend(dst.id, 2 * point + 1);
+        }
+    }
+
+    // ブロックの入口で生きていれば先頭の命令の読み込みから、出口で生きていれば最後の命令の書き込みまで
+    let cfg = Cfg::new(opcodes);
+    let (block_in, block_out) = block_liveness(&cfg, register_num);
+    let mut start = 0;
+    for (id, block) in cfg.blocks.iter().enumerate() {
+        if block.code.is_empty() {
+            continue;
+        }
+        let last = start + block.code.len() - 1;
+        for reg_id in block_in[id].iter() {
+            extend(reg_id, 2 * start);
+        }
+        for reg_id in block_out[id].iter() {
+            extend(reg_id, 2 * last + 1);
+        }
+        start = last + 1;
+    }
+
+    bounds.into_iter().enumerate()
+        .filter_map(|(reg_id, bound)| bound.map(|(start, end)| Interval { start, end, reg_id }))
+        .collect()
+}
+
+fn scan(opcodes: &[OpeCode], max_register_num: usize) -> Result<Scanned, AllocError> {
+    check_program(opcodes, max_register_num, MIN_REGISTER_NUM)?;
+
+    // 0から詰めた番号に付け直してからwebに分ける。以降のレジスタ番号はwebの番号
+    let renumbering = Renumbering::new(opcodes);
+    let opcodes = split_webs(&renumbering.apply(opcodes)).code;
+
+    // 始点の順
+    let mut intervals = live_intervals(&opcodes);
+    intervals.sort();
+
+    let mut reg_map: HashMap<usize, usize> = HashMap::new();
+    let mut spilled_reg: Vec<usize> = Vec::new();
+
+    // 残り2つは一時レジスタ
+    let mut free: BTreeSet<usize> = (1..max_register_num - 1).collect();
+    // 割り当て中の区間 (終点, レジスタ番号)
+    let mut active: BTreeSet<(usize, usize)> = BTreeSet::new();
+
+    for interval in intervals {
+        // 終わった区間のレジスタを空ける
+        while let Some(&(end, reg_id)) = active.iter().next() {
+            if end >= interval.start {
+                break;
+            }
+            active.remove(&(end, reg_id));
+            free.insert(reg_map[&reg_id]);
+        }
+
+        match free.iter().next().cloned() {
+            Some(color) => {
+                free.remove(&color);
+                reg_map.insert(interval.reg_id, color);
+                active.insert((interval.end, interval.reg_id));
+            },
+            None => {
+                // 終点が最も遠い区間をspillする
+                let &(end, reg_id) = active.iter().next_back().ok_or(AllocError::Uncolorable)?;
+                if end > interval.end {
+                    let color = reg_map.remove(&reg_id).unwrap();
+                    active.remove(&(end, reg_id));
+                    spilled_reg.push(reg_id);
+                    reg_map.insert(interval.reg_id, color);
+                    active.insert((interval.end, interval.reg_id));
+                } else {
+                    spilled_reg.push(interval.reg_id);
+                }
+            },
+        }
+    }
+
+    let spilled: HashSet<usize> = spilled_reg.iter().cloned().collect();
+    let (result, reg_addr_map) = rewrite(&opcodes, &reg_map, &spilled, max_register_num)?;
+
+    // 元の番号に戻す
+    let sparse = |map: HashMap<usize, usize>| map.into_iter().map(|(reg_id, value)| (renumbering.sparse(reg_id), value)).collect();
+    Ok(Scanned {
+        code: result,
+        ranges: renumbering.restore(&opcodes),
+        reg_map: sparse(reg_map),
+        spilled_reg: spilled_reg.into_iter().map(|reg_id| renumbering.sparse(reg_id)).collect(),
+        reg_addr_map: sparse(reg_addr_map),
+    })
+}
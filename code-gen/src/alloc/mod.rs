@@ -0,0 +1,292 @@
+//! レジスタ割り当て
+//!
+//! 割り当て方法は`RegisterAllocator`を実装し、`Registry`に登録する
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error;
+use std::fmt;
+
+use bitset::BitSet;
+use cfg::Cfg;
+use ir::{max_register_id, OpeCode};
+use liveness::block_liveness;
+use vm::DEFAULT_MEMORY_SIZE;
+
+mod chaitin;
+mod coalescing;
+mod linear_scan;
+mod naive;
+mod rewrite;
+mod spill_cost;
+
+pub use self::chaitin::{allocate_registers2, Chaitin, Coloring};
+pub use self::coalescing::{allocate_registers_coalescing, IteratedCoalescing};
+pub use self::linear_scan::{allocate_registers_linear_scan, LinearScan};
+pub use self::naive::{allocate_registers1, Naive};
+pub use self::spill_cost::{Candidate, Frequency, LowestNumber, SpillDecision, SpillHeuristic, UsesPerDegree};
+
+/// 割り当ての設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllocConfig {
+    /// 使える物理レジスタの数 (%1から%register_numまで)
+    pub register_num: usize,
+}
+
+impl AllocConfig {
+    pub fn new(register_num: usize) -> AllocConfig {
+        AllocConfig { register_num }
+    }
+}
+
+/// 割り当ての統計
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AllocStats {
+    /// 割り当て前の命令数
+    pub instructions_before: usize,
+    /// 割り当て後の命令数
+    pub instructions_after: usize,
+    /// 追加されたLoadの数
+    pub loads: usize,
+    /// 追加されたStoreの数
+    pub stores: usize,
+}
+
+/// 割り当ての結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Allocation {
+    /// 割り当て後のコード
+    pub code: Vec<OpeCode>,
+    /// 生存区間ごとにレジスタ番号を付け直した入力
+    ///
+    /// `assignment`, `spilled`, `slots`の仮想レジスタの番号はこのプログラムのもの
+    pub ranges: Vec<OpeCode>,
+    /// 仮想レジスタ -> 物理レジスタ
+    pub assignment: BTreeMap<usize, usize>,
+    /// メモリに置かれた仮想レジスタ
+    pub spilled: BTreeSet<usize>,
+    /// spillした仮想レジスタ -> 置き場所のアドレス
+    pub slots: BTreeMap<usize, i32>,
+    pub stats: AllocStats,
+    /// spillの候補を選んだ記録 (選んだ順)
+    pub spill_decisions: Vec<SpillDecision>,
+}
+
+impl Allocation {
+    pub fn new(input: &[OpeCode], code: Vec<OpeCode>, assignment: BTreeMap<usize, usize>, spilled: BTreeSet<usize>, slots: BTreeMap<usize, i32>) -> Allocation {
+        let loads = |opcodes: &[OpeCode]| opcodes.iter().filter(|op| matches!(**op, OpeCode::Load { .. })).count();
+        let stores = |opcodes: &[OpeCode]| opcodes.iter().filter(|op| matches!(**op, OpeCode::Store { .. })).count();
+
+        let stats = AllocStats {
+            instructions_before: input.len(),
+            instructions_after: code.len(),
+            loads: loads(&code).saturating_sub(loads(input)),
+            stores: stores(&code).saturating_sub(stores(input)),
+        };
+
+        Allocation { code, ranges: input.to_vec(), assignment, spilled, slots, stats, spill_decisions: Vec::new() }
+    }
+
+    /// 入力のレジスタ番号を付け直して割り当てたときに、付け直したプログラムを設定する
+    pub fn with_ranges(mut self, ranges: Vec<OpeCode>) -> Allocation {
+        self.ranges = ranges;
+        self
+    }
+
+    /// spillの候補を選んだ記録を設定する
+    pub fn with_spill_decisions(mut self, spill_decisions: Vec<SpillDecision>) -> Allocation {
+        self.spill_decisions = spill_decisions;
+        self
+    }
+}
+
+/// 割り当てに失敗した理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllocError {
+    /// 一時レジスタを含めて最低限必要なレジスタがない
+    TooFewRegisters { required: usize, available: usize },
+    /// spillできるレジスタが見つからず色を塗れない
+    Uncolorable,
+    /// index番目の命令で、書き込まれていないレジスタを読んでいる
+    UndefinedRegister { reg: usize, index: usize },
+    /// index番目の命令の分岐先のラベルがない
+    UndefinedLabel { label: String, index: usize },
+    EmptyProgram,
+    /// baseから始まるslots個のspill用のアドレスがVMのメモリに収まらない
+    NoRoomForSpillSlots { base: usize, slots: usize },
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AllocError::TooFewRegisters { required, available } => {
+                write!(f, "at least {} registers are required but only {} available", required, available)
+            },
+            AllocError::Uncolorable => write!(f, "interference graph cannot be colored"),
+            AllocError::UndefinedRegister { reg, index } => {
+                write!(f, "instruction {} reads %{} before it is defined", index, reg)
+            },
+            AllocError::UndefinedLabel { ref label, index } => {
+                write!(f, "instruction {} jumps to undefined label `{}`", index, label)
+            },
+            AllocError::EmptyProgram => write!(f, "program is empty"),
+            AllocError::NoRoomForSpillSlots { base, slots } => {
+                write!(f, "no room for spill slots: {} slot(s) from address {} do not fit in memory", slots, base)
+            },
+        }
+    }
+}
+
+impl error::Error for AllocError {}
+
+/// 一時レジスタを2つ取っておく割り当て方法 (naive, linear-scan, irc) に必要なレジスタの数
+///
+/// 一時レジスタ2つと、少なくとも1つの割り当て用のレジスタ。どの割り当て方法もこれだけあれば割り当てられる
+pub const MIN_REGISTER_NUM: usize = 3;
+
+/// レジスタ割り当ての方法
+pub trait RegisterAllocator {
+    fn name(&self) -> &'static str;
+    fn config(&self) -> &AllocConfig;
+    /// 割り当てに必要な最小のレジスタ数
+    fn min_register_num(&self) -> usize {
+        MIN_REGISTER_NUM
+    }
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError>;
+}
+
+pub type Constructor = fn(AllocConfig) -> Box<dyn RegisterAllocator>;
+
+/// 名前から割り当て方法を作る
+///
+/// `Registry::default()`にはこのクレートのすべての割り当て方法が登録されている
+pub struct Registry {
+    entries: Vec<(&'static str, Constructor)>,
+}
+
+impl Registry {
+    /// 空のRegistry
+    pub fn new() -> Registry {
+        Registry { entries: Vec::new() }
+    }
+
+    /// 同じ名前がすでにあれば置き換える
+    pub fn register(&mut self, name: &'static str, constructor: Constructor) {
+        match self.entries.iter_mut().find(|entry| entry.0 == name) {
+            Some(entry) => entry.1 = constructor,
+            None => self.entries.push((name, constructor)),
+        }
+    }
+
+    /// 登録順の名前
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries.iter().map(|entry| entry.0).collect()
+    }
+
+    pub fn create(&self, name: &str, config: AllocConfig) -> Option<Box<dyn RegisterAllocator>> {
+        self.entries.iter().find(|entry| entry.0 == name).map(|entry| (entry.1)(config))
+    }
+
+    /// 登録されているすべての割り当て方法を同じ設定で作る
+    pub fn create_all(&self, config: &AllocConfig) -> Vec<Box<dyn RegisterAllocator>> {
+        self.entries.iter().map(|entry| (entry.1)(config.clone())).collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        let mut registry = Registry::new();
+        registry.register("naive", |config| Box::new(Naive::new(config)));
+        registry.register("chaitin", |config| Box::new(Chaitin::new(config)));
+        registry.register("briggs", |config| Box::new(Chaitin::with_coloring(config, Coloring::Optimistic)));
+        registry.register("linear-scan", |config| Box::new(LinearScan::new(config)));
+        registry.register("irc", |config| Box::new(IteratedCoalescing::new(config)));
+        registry
+    }
+}
+
+// 割り当てられないプログラムを弾く
+//
+// requiredは割り当てに必要な最小のレジスタ数
+fn check_program(opcodes: &[OpeCode], register_num: usize, required: usize) -> Result<(), AllocError> {
+    if opcodes.is_empty() {
+        return Err(AllocError::EmptyProgram);
+    }
+    if register_num < required {
+        return Err(AllocError::TooFewRegisters { required, available: register_num });
+    }
+
+    // Cfg::newは分岐先のラベルがあることを前提にしている
+    let labels: HashSet<&str> = opcodes.iter().filter_map(|op| match *op {
+        OpeCode::Label { ref label } => Some(label.name.as_str()),
+        _ => None,
+    }).collect();
+    let undefined = opcodes.iter().enumerate()
+        .find_map(|(index, op)| op.target().filter(|target| !labels.contains(target.name.as_str())).map(|target| (index, target)));
+    if let Some((index, target)) = undefined {
+        return Err(AllocError::UndefinedLabel { label: target.name.clone(), index });
+    }
+
+    // 入口で生きているレジスタは、書き込まれずに読まれる経路がある
+    let cfg = Cfg::new(opcodes);
+    let (block_in, _) = block_liveness(&cfg, max_register_id(opcodes) + 1);
+    let undefined = block_in[cfg.entry].iter()
+        .filter_map(|reg_id| first_undefined_use(&cfg, reg_id).map(|index| (index, reg_id)))
+        .min();
+    match undefined {
+        Some((index, reg)) => Err(AllocError::UndefinedRegister { reg, index }),
+        None => Ok(()),
+    }
+}
+
+// 入口から書き込まずに進んでreg_idを読む命令のうち、最も前にあるものの位置
+fn first_undefined_use(cfg: &Cfg, reg_id: usize) -> Option<usize> {
+    let mut block_start = Vec::with_capacity(cfg.blocks.len());
+    let mut start = 0;
+    for block in &cfg.blocks {
+        block_start.push(start);
+        start += block.code.len();
+    }
+
+    let mut first = None;
+    let mut visited = BitSet::new(cfg.blocks.len());
+    let mut stack = vec![cfg.entry];
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let block = &cfg.blocks[id];
+        let reached = block.code.iter().enumerate().find(|&(_, opcode)| {
+            opcode.srcs().iter().any(|reg| reg.id == reg_id) || opcode.dst().is_some_and(|reg| reg.id == reg_id)
+        });
+        match reached {
+            Some((offset, opcode)) => {
+                if opcode.srcs().iter().any(|reg| reg.id == reg_id) {
+                    let index = block_start[id] + offset;
+                    first = Some(first.map_or(index, |first: usize| first.min(index)));
+                }
+            },
+            None => stack.extend(block.succs.iter().cloned()),
+        }
+    }
+    first
+}
+
+// spillに使うアドレスの先頭 (プログラムが使う最大のアドレスの次)
+//
+// そこからslots個のアドレスがVMのメモリに収まらなければエラー
+fn spill_base(opcodes: &[OpeCode], slots: usize) -> Result<usize, AllocError> {
+    let base = opcodes.iter().filter_map(|op| match *op {
+        OpeCode::Store { dst: ref addr, .. } | OpeCode::Load { src: ref addr, .. } => Some((i64::from(addr.value) + 1).max(0) as usize),
+        _ => None,
+    }).max().unwrap_or(0);
+
+    if slots > 0 && base + slots > DEFAULT_MEMORY_SIZE {
+        return Err(AllocError::NoRoomForSpillSlots { base, slots });
+    }
+    Ok(base)
+}
+
+// プログラム中の仮想レジスタ
+fn virtual_registers(opcodes: &[OpeCode]) -> BTreeSet<usize> {
+    opcodes.iter().flat_map(|op| op.registers()).map(|reg| reg.id).collect()
+}
@@ -0,0 +1,56 @@
+use std::collections::{HashMap, HashSet};
+
+use alloc::{check_program, virtual_registers, AllocConfig, AllocError, Allocation, RegisterAllocator, MIN_REGISTER_NUM};
+use alloc::rewrite::rewrite;
+use ir::OpeCode;
+
+/// 先頭からN-2までのレジスタを割り当てて、残りはStore, Loadしてメモリに置く
+pub fn allocate_registers1(opcodes: Vec<OpeCode>, register_num: usize) -> Result<Vec<OpeCode>, AllocError> {
+    spill_registers(opcodes, register_num).map(|result| result.0)
+}
+
+// 割り当て後のコードと、レジスタ番号 -> アドレスを返す
+fn spill_registers(opcodes: Vec<OpeCode>, register_num: usize) -> Result<(Vec<OpeCode>, HashMap<usize, usize>), AllocError> {
+    check_program(&opcodes, register_num, MIN_REGISTER_NUM)?;
+
+    // %(N-2)までは同じ番号の物理レジスタ、それより後はメモリに置く
+    let (assigned, spilled): (Vec<usize>, Vec<usize>) = virtual_registers(&opcodes).into_iter()
+        .partition(|&reg_id| reg_id <= register_num - 2);
+    let reg_map: HashMap<usize, usize> = assigned.into_iter().map(|reg_id| (reg_id, reg_id)).collect();
+    let spilled: HashSet<usize> = spilled.into_iter().collect();
+
+    rewrite(&opcodes, &reg_map, &spilled, register_num)
+}
+
+/// 先頭から順に割り当てる素朴な方法
+pub struct Naive {
+    config: AllocConfig,
+}
+
+impl Naive {
+    pub fn new(config: AllocConfig) -> Naive {
+        Naive { config }
+    }
+}
+
+impl RegisterAllocator for Naive {
+    fn name(&self) -> &'static str {
+        "naive"
+    }
+
+    fn config(&self) -> &AllocConfig {
+        &self.config
+    }
+
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError> {
+        let register_num = self.config.register_num;
+        let (code, reg_addr_map) = spill_registers(opcodes.to_vec(), register_num)?;
+        let (assigned, spilled) = virtual_registers(opcodes).into_iter()
+            .partition::<Vec<_>, _>(|&reg_id| reg_id <= register_num - 2);
+
+        let assignment = assigned.into_iter().map(|reg_id| (reg_id, reg_id)).collect();
+        let slots = reg_addr_map.into_iter().map(|(reg_id, addr)| (reg_id, addr as i32)).collect();
+
+        Ok(Allocation::new(opcodes, code, assignment, spilled.into_iter().collect(), slots))
+    }
+}
@@ -0,0 +1,211 @@
+//! 割り当ての結果に従ってプログラムを書き換える
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use alloc::{spill_base, AllocError};
+use ir::{Integer, OpeCode, Register};
+
+/// reg_mapの色の物理レジスタに置き換え、spilledのレジスタはメモリに置く
+///
+/// spillしたレジスタは使うたびに一時レジスタ (%(N-1), %N) にLoadし、書き込むたびにStoreする。
+/// 読み書きするレジスタが同じになったMovは出力しない。
+/// 書き換えたコードと、spillしたレジスタ -> アドレスを返す
+pub fn rewrite(opcodes: &[OpeCode], reg_map: &HashMap<usize, usize>, spilled: &HashSet<usize>, max_register_num: usize) -> Result<(Vec<OpeCode>, HashMap<usize, usize>), AllocError> {
+    let mut result: Vec<OpeCode> = Vec::new();
+
+    // register id -> address
+    let mut reg_addr_map: HashMap<usize, usize> = HashMap::new();
+    // プログラムが使うアドレスと重ならないようにする
+    let spill_base = spill_base(opcodes, spilled.len())?;
+
+    // for spilled registers
+    let alloc_dst_reg = |reg_id: usize, original_reg_id: usize, reg_addr_map: &mut HashMap<usize, usize>| {
+        let temp_reg = max_register_num - 1;
+
+        if !spilled.contains(&reg_id) {
+            (reg!(reg_id), None)
+        } else {
+            let new_addr = spill_base + reg_addr_map.len();
+            reg_addr_map.entry(original_reg_id).or_insert(new_addr);
+
+            (reg!(temp_reg), Some(Integer::new(*reg_addr_map.get(&original_reg_id).unwrap() as i32)))
+        }
+    };
+
+    let alloc_src_reg = |reg_id: usize, original_reg_id: usize, temp_reg: usize, reg_addr_map: &mut HashMap<usize, usize>, result: &mut Vec<OpeCode>| {
+        if !spilled.contains(&reg_id) {
+            None
+        } else {
+            let new_addr = spill_base + reg_addr_map.len();
+            reg_addr_map.entry(original_reg_id).or_insert(new_addr);
+
+            let addr = Integer::new(*reg_addr_map.get(&original_reg_id).unwrap() as i32);
+            result.push(OpeCode::Load{ dst: reg!(temp_reg), src: addr });
+
+            Some(reg!(temp_reg))
+        }
+    };
+
+    let color_of = |reg_id: usize| reg_map.get(&reg_id).map(|&color| reg!(color)).ok_or(AllocError::Uncolorable);
+
+    for opcode in opcodes {
+        let opcode = opcode.clone();
+        match opcode {
+            OpeCode::LdI { dst, value } => {
+                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
+                    (reg, None) => {
+                        result.push(OpeCode::LdI{ dst: color_of(reg.id)?, value });
+                    },
+                    (reg, Some(addr)) => {
+                        result.push(OpeCode::LdI{ dst: reg.clone(), value});
+                        result.push(OpeCode::Store{ dst: addr, src: reg});
+                    },
+                }
+            },
+            OpeCode::Add { dst, src1, src2 } => {
+
+                let src1 = alloc_src_reg(src1.id, src1.id, max_register_num - 1, &mut reg_addr_map, &mut result)
+                           .map(Ok).unwrap_or_else(|| color_of(src1.id))?;
+                let src2 = alloc_src_reg(src2.id, src2.id, max_register_num,     &mut reg_addr_map, &mut result)
+                           .map(Ok).unwrap_or_else(|| color_of(src2.id))?;
+
+                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
+                    (reg, None) => {
+                        result.push(OpeCode::Add{ dst: color_of(reg.id)?, src1, src2 });
+                    },
+                    (reg, Some(addr)) => {
+                        result.push(OpeCode::Add{ dst: reg.clone(), src1, src2 });
+                        result.push(OpeCode::Store{ dst: addr, src: reg });
+                    },
+                }
+            },
+            OpeCode::Store { dst, src } => {
+                let src = alloc_src_reg(src.id, src.id, max_register_num, &mut reg_addr_map, &mut result)
+                           .map(Ok).unwrap_or_else(|| color_of(src.id))?;
+                result.push(OpeCode::Store{ dst, src });
+            },
+            OpeCode::Load { dst, src } => {
+                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
+                    (reg, None) => {
+                        result.push(OpeCode::Load{ dst: color_of(reg.id)?, src });
+                    },
+                    (reg, Some(addr)) => {
+                        result.push(OpeCode::Load{ dst: reg.clone(), src });
+                        result.push(OpeCode::Store{ dst: addr, src: reg});
+                    },
+                }
+            },
+            OpeCode::Print { src } => {
+                let src = alloc_src_reg(src.id, src.id, max_register_num, &mut reg_addr_map, &mut result)
+                           .map(Ok).unwrap_or_else(|| color_of(src.id))?;
+                result.push(OpeCode::Print{ src });
+            },
+            OpeCode::Mov { dst, src } => {
+                let src = alloc_src_reg(src.id, src.id, max_register_num, &mut reg_addr_map, &mut result)
+                           .map(Ok).unwrap_or_else(|| color_of(src.id))?;
+
+                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
+                    // 同じレジスタへのコピーは消す
+                    (reg, None) => {
+                        let dst = color_of(reg.id)?;
+                        if dst != src {
+                            result.push(OpeCode::Mov{ dst, src });
+                        }
+                    },
+                    (reg, Some(addr)) => {
+                        result.push(OpeCode::Mov{ dst: reg.clone(), src });
+                        result.push(OpeCode::Store{ dst: addr, src: reg });
+                    },
+                }
+            },
+            OpeCode::Label { .. } | OpeCode::Jmp { .. } => result.push(opcode),
+            OpeCode::Beq { .. } | OpeCode::Bne { .. } | OpeCode::Blt { .. } => {
+                // 1つ目を%(N-1)に、2つ目を%Nに読み込む
+                let mut srcs = Vec::new();
+                for (reg, temp_reg) in opcode.srcs().into_iter().zip(max_register_num - 1..) {
+                    srcs.push(alloc_src_reg(reg.id, reg.id, temp_reg, &mut reg_addr_map, &mut result)
+                              .map(Ok).unwrap_or_else(|| color_of(reg.id))?);
+                }
+                let mut srcs = srcs.into_iter();
+                let opcode = opcode.map_registers(|_| srcs.next().unwrap());
+                result.push(opcode);
+            },
+        }
+    }
+
+
+    Ok((result, reg_addr_map))
+}
+
+/// slotsのレジスタ (-> アドレス) をメモリに置くコードを挿入する
+///
+/// 読む命令の直前で新しい仮想レジスタにLoadし、書き込む命令の直後に新しい仮想レジスタからStoreする。
+/// 新しいレジスタにはnext_idから順に番号を振る。
+/// 書き換えたコードと、その各命令がopcodesの何番目の命令から作られたかを返す
+pub fn insert_spill_code(opcodes: &[OpeCode], slots: &HashMap<usize, usize>, next_id: &mut usize) -> (Vec<OpeCode>, Vec<usize>) {
+    let mut result = Vec::with_capacity(opcodes.len());
+    let mut origins = Vec::with_capacity(opcodes.len());
+
+    for (index, opcode) in opcodes.iter().enumerate() {
+        // 読むレジスタ -> Loadした一時レジスタ (同じレジスタを2回読むなら1回だけLoadする)
+        let mut loaded: HashMap<usize, Register> = HashMap::new();
+        for src in opcode.srcs() {
+            if let Some(&addr) = slots.get(&src.id) {
+                if let Entry::Vacant(entry) = loaded.entry(src.id) {
+                    let temp = reg!(*next_id);
+                    *next_id += 1;
+                    result.push(OpeCode::Load { dst: temp.clone(), src: Integer::new(addr as i32) });
+                    origins.push(index);
+                    entry.insert(temp);
+                }
+            }
+        }
+
+        let mut stored = None;
+        let opcode = opcode.map_operands(
+            |src| loaded.get(&src.id).cloned().unwrap_or_else(|| src.clone()),
+            |dst| match slots.get(&dst.id) {
+                Some(&addr) => {
+                    let temp = reg!(*next_id);
+                    *next_id += 1;
+                    stored = Some(OpeCode::Store { dst: Integer::new(addr as i32), src: temp.clone() });
+                    temp
+                },
+                None => dst.clone(),
+            });
+        result.push(opcode);
+        origins.push(index);
+        if let Some(store) = stored {
+            result.push(store);
+            origins.push(index);
+        }
+    }
+
+    (result, origins)
+}
+
+/// すべてのレジスタをreg_mapの色の物理レジスタに置き換える
+///
+/// 読み書きするレジスタが同じになったMovは出力しない
+pub fn apply_colors(opcodes: &[OpeCode], reg_map: &HashMap<usize, usize>) -> Result<Vec<OpeCode>, AllocError> {
+    let mut result = Vec::with_capacity(opcodes.len());
+    for opcode in opcodes {
+        let mut uncolored = false;
+        let opcode = opcode.map_registers(|reg| match reg_map.get(&reg.id) {
+            Some(&color) => reg!(color),
+            None => {
+                uncolored = true;
+                reg.clone()
+            },
+        });
+        if uncolored {
+            return Err(AllocError::Uncolorable);
+        }
+        match opcode.as_move() {
+            Some((dst, src)) if dst == src => {},
+            _ => result.push(opcode),
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,185 @@
+//! spillするレジスタの選び方
+//!
+//! 読み書きの回数を命令の実行回数の見積もりで重み付けし、それを`SpillHeuristic`でコストに直す
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use cfg::Cfg;
+use ir::OpeCode;
+
+/// 命令の実行回数の見積もり方
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frequency {
+    /// ループの深さがdの命令は10^d回実行する
+    LoopDepth,
+    /// 命令ごとに測った実行回数 (`vm::ExecutionResult::counts`)
+    Profile(Vec<usize>),
+}
+
+impl Frequency {
+    /// 命令ごとの実行回数の見積もり
+    pub fn estimate(&self, opcodes: &[OpeCode]) -> Vec<f64> {
+        match *self {
+            Frequency::LoopDepth => {
+                let cfg = Cfg::new(opcodes);
+                let depths = cfg.loop_depths();
+                let mut frequencies = Vec::with_capacity(opcodes.len());
+                for (block, &depth) in cfg.blocks.iter().zip(&depths) {
+                    let frequency = 10f64.powi(depth as i32);
+                    frequencies.extend(block.code.iter().map(|_| frequency));
+                }
+                frequencies
+            },
+            Frequency::Profile(ref counts) => {
+                (0..opcodes.len()).map(|index| counts.get(index).cloned().unwrap_or(0) as f64).collect()
+            },
+        }
+    }
+}
+
+/// spillの候補
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// レジスタ番号 (`Allocation::ranges`のもの)
+    pub reg: usize,
+    /// 読む命令の数
+    pub uses: usize,
+    /// 書き込む命令の数
+    pub defs: usize,
+    /// 実行回数の見積もりで重み付けした読み書きの回数
+    pub weight: f64,
+    /// 干渉グラフに残っている隣接ノードの数
+    pub degree: usize,
+}
+
+/// spillするレジスタの選び方
+///
+/// コストが最も小さい候補をspillする (同じなら番号の小さいもの)
+pub trait SpillHeuristic {
+    fn name(&self) -> &'static str;
+    /// candidateをspillしたときのコストの見積もり
+    fn cost(&self, candidate: &Candidate) -> f64;
+}
+
+/// 重み付けした読み書きの回数 / 次数
+///
+/// 使われる回数が少なく、多くのレジスタと干渉するものからspillする
+pub struct UsesPerDegree;
+
+impl SpillHeuristic for UsesPerDegree {
+    fn name(&self) -> &'static str {
+        "uses-per-degree"
+    }
+
+    fn cost(&self, candidate: &Candidate) -> f64 {
+        candidate.weight / candidate.degree.max(1) as f64
+    }
+}
+
+/// 番号の最も小さいレジスタからspillする
+pub struct LowestNumber;
+
+impl SpillHeuristic for LowestNumber {
+    fn name(&self) -> &'static str {
+        "lowest-number"
+    }
+
+    fn cost(&self, candidate: &Candidate) -> f64 {
+        candidate.reg as f64
+    }
+}
+
+/// spillするレジスタを選んだときの記録
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpillDecision {
+    pub candidate: Candidate,
+    pub cost: f64,
+    /// 候補の数
+    pub candidates: usize,
+    /// 実際にspillしたか (楽観的な彩色では、選んでも塗れることがある)
+    pub spilled: bool,
+}
+
+impl fmt::Display for SpillDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let candidate = &self.candidate;
+        write!(f, "%{}: cost {:.3} (uses {}, defs {}, weight {}, degree {}) out of {} candidate(s)",
+               candidate.reg, self.cost, candidate.uses, candidate.defs, candidate.weight, candidate.degree, self.candidates)?;
+        if !self.spilled {
+            write!(f, ", colored")?;
+        }
+        Ok(())
+    }
+}
+
+// レジスタごとの (読む回数, 書き込む回数, 重み付けした読み書きの回数)
+pub fn occurrences(opcodes: &[OpeCode], frequencies: &[f64], register_num: usize) -> Vec<(usize, usize, f64)> {
+    let mut occurrences = vec![(0, 0, 0.0); register_num];
+    for (opcode, &frequency) in opcodes.iter().zip(frequencies) {
+        for src in opcode.srcs() {
+            occurrences[src.id].0 += 1;
+            occurrences[src.id].2 += frequency;
+        }
+        if let Some(dst) = opcode.dst() {
+            occurrences[dst.id].1 += 1;
+            occurrences[dst.id].2 += frequency;
+        }
+    }
+    occurrences
+}
+
+// 全順序を付けたコスト
+#[derive(Clone, Copy)]
+struct Cost(f64);
+
+impl PartialEq for Cost {
+    fn eq(&self, other: &Cost) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Cost) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Cost) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// コストの小さい順 (同じなら番号の小さい順) に候補を取り出す
+///
+/// 次数が変わった候補は入れ直す。古いコストのものは取り出すときに捨てる
+pub struct SpillQueue<'a> {
+    heuristic: &'a dyn SpillHeuristic,
+    heap: BinaryHeap<Reverse<(Cost, usize)>>,
+}
+
+impl<'a> SpillQueue<'a> {
+    pub fn new(heuristic: &'a dyn SpillHeuristic) -> SpillQueue<'a> {
+        SpillQueue { heuristic, heap: BinaryHeap::new() }
+    }
+
+    pub fn push(&mut self, candidate: &Candidate) {
+        self.heap.push(Reverse((Cost(self.heuristic.cost(candidate)), candidate.reg)));
+    }
+
+    /// current(reg)は今の候補 (候補でなくなっていればNone)
+    pub fn pop<F: Fn(usize) -> Option<Candidate>>(&mut self, current: F) -> Option<(Candidate, f64)> {
+        while let Some(Reverse((cost, reg))) = self.heap.pop() {
+            if let Some(candidate) = current(reg) {
+                if Cost(self.heuristic.cost(&candidate)) == cost {
+                    return Some((candidate, cost.0));
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,124 @@
+//! 固定長のビット集合
+
+use std::fmt;
+
+/// 0からlen - 1までの整数の集合
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    /// 空の集合
+    pub fn new(len: usize) -> BitSet {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    /// 入れられる整数の上限 (含まない)
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        i < self.len && self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// 新しく入れたらtrue
+    pub fn insert(&mut self, i: usize) -> bool {
+        assert!(i < self.len, "{} is out of range 0..{}", i, self.len);
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        let inserted = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        inserted
+    }
+
+    /// 入っていたらtrue
+    pub fn remove(&mut self, i: usize) -> bool {
+        if i >= self.len {
+            return false;
+        }
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        let removed = self.words[word] & bit != 0;
+        self.words[word] &= !bit;
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        for word in &mut self.words {
+            *word = 0;
+        }
+    }
+
+    /// otherを足す。増えたらtrue
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        let mut changed = false;
+        for (word, &other) in self.words.iter_mut().zip(&other.words) {
+            let new = *word | other;
+            changed |= new != *word;
+            *word = new;
+        }
+        changed
+    }
+
+    /// otherを取り除く
+    pub fn difference_with(&mut self, other: &BitSet) {
+        for (word, &other) in self.words.iter_mut().zip(&other.words) {
+            *word &= !other;
+        }
+    }
+
+    /// otherとの共通部分だけを残す
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        for (word, &other) in self.words.iter_mut().zip(&other.words) {
+            *word &= other;
+        }
+    }
+
+    /// 要素の数
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// 小さい順
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { words: &self.words, index: 0, word: self.words.first().cloned().unwrap_or(0) }
+    }
+}
+
+pub struct Iter<'a> {
+    words: &'a [u64],
+    // wordがwords[index]の残り
+    index: usize,
+    word: u64,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.word == 0 {
+            self.index += 1;
+            if self.index >= self.words.len() {
+                return None;
+            }
+            self.word = self.words[self.index];
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+        Some(self.index * 64 + bit)
+    }
+}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,325 @@
+//! 基本ブロックと制御フローグラフ
+//!
+//! 先頭に空の入口ブロック、最後に空の出口ブロックを置く。
+//! プログラムの最後から抜けるブロックは出口ブロックに進む
+
+use std::collections::{HashMap, HashSet};
+
+use bitset::BitSet;
+use ir::{Label, OpeCode};
+
+/// 基本ブロック
+///
+/// ラベルは先頭にだけ、分岐命令は最後にだけ置かれる
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub code: Vec<OpeCode>,
+    /// 分岐しなかったときに進むブロック
+    pub fallthrough: Option<usize>,
+    pub preds: Vec<usize>,
+    pub succs: Vec<usize>,
+}
+
+impl BasicBlock {
+    fn new(code: Vec<OpeCode>) -> BasicBlock {
+        BasicBlock { code, fallthrough: None, preds: Vec::new(), succs: Vec::new() }
+    }
+
+    /// 先頭のラベル
+    pub fn label(&self) -> Option<&Label> {
+        match self.code.first() {
+            Some(OpeCode::Label { label }) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// 最後の分岐命令の分岐先
+    pub fn target(&self) -> Option<&Label> {
+        self.code.last().and_then(|op| op.target())
+    }
+}
+
+/// 制御フローグラフ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub entry: usize,
+    pub exit: usize,
+    // 新しいラベルの通し番号
+    next_label: usize,
+}
+
+impl Cfg {
+    /// プログラムを基本ブロックに分ける
+    ///
+    /// 分岐先のラベルはすべて定義されていること (`parser::parse`の結果ならそうなっている)
+    pub fn new(opcodes: &[OpeCode]) -> Cfg {
+        let mut blocks = vec![BasicBlock::new(Vec::new())];
+
+        let mut code = Vec::new();
+        for opcode in opcodes {
+            if let OpeCode::Label { .. } = *opcode {
+                if !code.is_empty() {
+                    blocks.push(BasicBlock::new(code));
+                    code = Vec::new();
+                }
+            }
+            code.push(opcode.clone());
+            if opcode.target().is_some() {
+                blocks.push(BasicBlock::new(code));
+                code = Vec::new();
+            }
+        }
+        if !code.is_empty() {
+            blocks.push(BasicBlock::new(code));
+        }
+        blocks.push(BasicBlock::new(Vec::new()));
+
+        let exit = blocks.len() - 1;
+        for (id, block) in blocks.iter_mut().enumerate() {
+            let falls_through = block.code.last().is_none_or(OpeCode::falls_through);
+            if id != exit && falls_through {
+                block.fallthrough = Some(id + 1);
+            }
+        }
+
+        let mut cfg = Cfg { blocks, entry: 0, exit, next_label: 0 };
+        cfg.rebuild_edges();
+        cfg
+    }
+
+    // fallthroughと分岐先からpreds, succsを作り直す
+    fn rebuild_edges(&mut self) {
+        let labels: HashMap<String, usize> = self.blocks.iter().enumerate()
+            .filter_map(|(id, block)| block.label().map(|label| (label.name.clone(), id)))
+            .collect();
+
+        for block in &mut self.blocks {
+            block.preds.clear();
+            block.succs.clear();
+        }
+        for id in 0..self.blocks.len() {
+            let mut succs: Vec<usize> = self.blocks[id].fallthrough.into_iter().collect();
+            if let Some(target) = self.blocks[id].target() {
+                let target = *labels.get(&target.name).unwrap_or_else(|| panic!("label `{}` is not defined", target));
+                if !succs.contains(&target) {
+                    succs.push(target);
+                }
+            }
+            for &succ in &succs {
+                self.blocks[succ].preds.push(id);
+            }
+            self.blocks[id].succs = succs;
+        }
+    }
+
+    /// 入口から辿った帰りがけ順 (到達できないブロックは含まない)
+    pub fn postorder(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut visited = vec![false; self.blocks.len()];
+        // (ブロック, 次に見るsuccsの位置)
+        let mut stack = vec![(self.entry, 0)];
+        visited[self.entry] = true;
+
+        while let Some(&mut (id, ref mut next)) = stack.last_mut() {
+            match self.blocks[id].succs.get(*next) {
+                Some(&succ) => {
+                    *next += 1;
+                    if !visited[succ] {
+                        visited[succ] = true;
+                        stack.push((succ, 0));
+                    }
+                },
+                None => {
+                    order.push(id);
+                    stack.pop();
+                },
+            }
+        }
+        order
+    }
+
+    /// 逆帰りがけ順
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = self.postorder();
+        order.reverse();
+        order
+    }
+
+    /// 各ブロックを支配するブロックの集合 (自分自身を含む)
+    ///
+    /// 到達できないブロックはすべてのブロックに支配されるとみなす
+    pub fn dominators(&self) -> Vec<BitSet> {
+        let block_num = self.blocks.len();
+        let mut all = BitSet::new(block_num);
+        for id in 0..block_num {
+            all.insert(id);
+        }
+        let mut dominators = vec![all; block_num];
+        dominators[self.entry] = BitSet::new(block_num);
+        dominators[self.entry].insert(self.entry);
+
+        let order = self.reverse_postorder();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in order.iter().filter(|&&id| id != self.entry) {
+                let mut dominator = dominators[self.blocks[id].preds[0]].clone();
+                for &pred in &self.blocks[id].preds[1..] {
+                    dominator.intersect_with(&dominators[pred]);
+                }
+                dominator.insert(id);
+                if dominator != dominators[id] {
+                    dominators[id] = dominator;
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+
+    /// 各ブロックを含む自然ループの数
+    ///
+    /// 後退辺 (自分を支配するブロックへの辺) ごとにループを作り、ヘッダが同じループは1つにまとめる
+    pub fn loop_depths(&self) -> Vec<usize> {
+        let block_num = self.blocks.len();
+        let dominators = self.dominators();
+        let mut reachable = BitSet::new(block_num);
+        for id in self.postorder() {
+            reachable.insert(id);
+        }
+
+        // ヘッダ -> ループに含まれるブロック
+        let mut bodies: HashMap<usize, BitSet> = HashMap::new();
+        for id in reachable.iter() {
+            for &header in self.blocks[id].succs.iter().filter(|&&succ| dominators[id].contains(succ)) {
+                let body = bodies.entry(header).or_insert_with(|| BitSet::new(block_num));
+                body.insert(header);
+                // ヘッダを通らずに後退辺の元に着くブロック
+                let mut stack = vec![id];
+                while let Some(block) = stack.pop() {
+                    if body.insert(block) {
+                        stack.extend(self.blocks[block].preds.iter().cloned().filter(|&pred| reachable.contains(pred)));
+                    }
+                }
+            }
+        }
+
+        (0..block_num).map(|id| bodies.values().filter(|body| body.contains(id)).count()).collect()
+    }
+
+    /// 入口から到達できないブロックを取り除く (出口ブロックは残す)
+    ///
+    /// ブロックの番号は付け直される
+    pub fn remove_unreachable(&mut self) {
+        let mut reachable: HashSet<usize> = self.postorder().into_iter().collect();
+        reachable.insert(self.exit);
+
+        let mut renumber = HashMap::new();
+        let blocks = self.blocks.drain(..).enumerate()
+            .filter(|&(id, _)| reachable.contains(&id))
+            .enumerate()
+            .map(|(new_id, (id, block))| {
+                renumber.insert(id, new_id);
+                block
+            })
+            .collect();
+        self.blocks = blocks;
+
+        for block in &mut self.blocks {
+            block.fallthrough = block.fallthrough.map(|id| renumber[&id]);
+        }
+        self.entry = renumber[&self.entry];
+        self.exit = renumber[&self.exit];
+        self.rebuild_edges();
+    }
+
+    /// 複数の後続を持つブロックから複数の先行を持つブロックへの辺に、空のブロックを挟む
+    pub fn split_critical_edges(&mut self) {
+        let mut edges = Vec::new();
+        for (from, block) in self.blocks.iter().enumerate() {
+            if block.succs.len() > 1 {
+                edges.extend(block.succs.iter().filter(|&&to| self.blocks[to].preds.len() > 1).map(|&to| (from, to)));
+            }
+        }
+
+        for (from, to) in edges {
+            let label = self.fresh_label();
+            let mut middle = BasicBlock::new(vec![OpeCode::Label { label: label.clone() }]);
+            middle.fallthrough = Some(to);
+            let middle_id = self.blocks.len();
+            self.blocks.push(middle);
+
+            if self.blocks[from].fallthrough == Some(to) {
+                self.blocks[from].fallthrough = Some(middle_id);
+            } else {
+                let branch = self.blocks[from].code.pop().unwrap();
+                self.blocks[from].code.push(retarget(&branch, label));
+            }
+        }
+        self.rebuild_edges();
+    }
+
+    // プログラム中のラベルと重ならない名前
+    fn fresh_label(&mut self) -> Label {
+        let used: HashSet<String> = self.blocks.iter()
+            .filter_map(|block| block.label().map(|label| label.name.clone()))
+            .collect();
+        loop {
+            let name = format!(".L{}", self.next_label);
+            self.next_label += 1;
+            if !used.contains(&name) {
+                return Label::new(name);
+            }
+        }
+    }
+
+    // ブロックのラベル (なければ付ける)
+    fn label_of(&mut self, id: usize) -> Label {
+        if let Some(label) = self.blocks[id].label() {
+            return label.clone();
+        }
+        let label = self.fresh_label();
+        self.blocks[id].code.insert(0, OpeCode::Label { label: label.clone() });
+        label
+    }
+
+    /// ブロックの順に命令を並べ直す
+    ///
+    /// 出口ブロックは最後に置き、次のブロックに進まないfallthroughにはJmpを足す
+    pub fn to_opcodes(&self) -> Vec<OpeCode> {
+        let mut cfg = self.clone();
+        let mut layout: Vec<usize> = (0..cfg.blocks.len()).filter(|&id| id != cfg.exit).collect();
+        layout.push(cfg.exit);
+
+        // 先に必要なラベルを付けておく
+        let mut jumps = HashMap::new();
+        for (i, &id) in layout.iter().enumerate() {
+            if let Some(next) = cfg.blocks[id].fallthrough {
+                if layout.get(i + 1) != Some(&next) {
+                    jumps.insert(id, cfg.label_of(next));
+                }
+            }
+        }
+
+        let mut opcodes = Vec::new();
+        for id in layout {
+            opcodes.extend(cfg.blocks[id].code.iter().cloned());
+            if let Some(target) = jumps.remove(&id) {
+                opcodes.push(OpeCode::Jmp { target });
+            }
+        }
+        opcodes
+    }
+}
+
+// 分岐先だけを置き換えた命令
+fn retarget(branch: &OpeCode, target: Label) -> OpeCode {
+    match *branch {
+        OpeCode::Jmp { .. } => OpeCode::Jmp { target },
+        OpeCode::Beq { ref src1, ref src2, .. } => OpeCode::Beq { src1: src1.clone(), src2: src2.clone(), target },
+        OpeCode::Bne { ref src1, ref src2, .. } => OpeCode::Bne { src1: src1.clone(), src2: src2.clone(), target },
+        OpeCode::Blt { ref src1, ref src2, .. } => OpeCode::Blt { src1: src1.clone(), src2: src2.clone(), target },
+        _ => unreachable!("not a branch: {}", branch),
+    }
+}
@@ -0,0 +1,350 @@
+//! 割り当ての結果が正しいかを静的に調べる
+//!
+//! 元のプログラムと割り当て後のプログラムを先頭から対応させ、
+//! 各物理レジスタとspill用のアドレスにどの仮想レジスタの値が入っているかを追う
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use alloc::Allocation;
+use ir::{OpeCode, Register};
+use liveness::Liveness;
+use renumber::Renumbering;
+
+/// 見つかった誤り
+///
+/// `index`は割り当て後のプログラムの位置、`original_index`は元のプログラムの位置
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// 範囲外の物理レジスタを使っている
+    OutOfBudget { index: usize, reg: usize },
+    /// 同時に生きている2つの仮想レジスタが同じ物理レジスタに割り当てられている
+    Interference { original_index: usize, reg1: usize, reg2: usize, phys: usize },
+    /// 元のプログラムの命令と対応しない
+    Unaligned { index: usize },
+    /// 元のプログラムの命令が割り当て後のプログラムにない
+    Missing { original_index: usize },
+    /// まだStoreしていないspill用のアドレスからLoadしている
+    ReloadBeforeStore { index: usize, addr: i32 },
+    /// 仮想レジスタregの値ではなく、別のアドレスからLoadした値を読んでいる
+    WrongSlot { index: usize, reg: usize, addr: i32 },
+    /// 物理レジスタphysにある仮想レジスタregの値が上書きされている
+    Clobbered { index: usize, reg: usize, phys: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::OutOfBudget { index, reg } => write!(f, "instruction {}: %{} is out of budget", index, reg),
+            Violation::Interference { original_index, reg1, reg2, phys } => {
+                write!(f, "original instruction {}: %{} and %{} are both live in %{}", original_index, reg1, reg2, phys)
+            },
+            Violation::Unaligned { index } => write!(f, "instruction {}: does not match the original program", index),
+            Violation::Missing { original_index } => write!(f, "original instruction {}: missing", original_index),
+            Violation::ReloadBeforeStore { index, addr } => {
+                write!(f, "instruction {}: reloads address {} before it is stored", index, addr)
+            },
+            Violation::WrongSlot { index, reg, addr } => {
+                write!(f, "instruction {}: reads %{} reloaded from the wrong address {}", index, reg, addr)
+            },
+            Violation::Clobbered { index, reg, phys } => {
+                write!(f, "instruction {}: %{} no longer holds %{}", index, phys, reg)
+            },
+        }
+    }
+}
+
+// 物理レジスタやspill用のアドレスに入っている値
+#[derive(Clone, PartialEq)]
+struct Value {
+    // 仮想レジスタと、それを書き込んだ元のプログラムの命令の位置
+    // (消えたMovでコピーされた値は複数の仮想レジスタが持つ)
+    defs: Vec<(usize, usize)>,
+    // spill用のアドレスからLoadした値ならそのアドレス
+    slot: Option<i32>,
+}
+
+impl Value {
+    fn new(reg: usize, def: usize) -> Value {
+        Value { defs: vec![(reg, def)], slot: None }
+    }
+
+    // 仮想レジスタregのdefで書き込んだ値か
+    fn holds(&self, reg: usize, def: Option<usize>) -> bool {
+        def.is_some_and(|def| self.defs.contains(&(reg, def)))
+    }
+}
+
+// レジスタ以外が同じ命令か
+fn same_shape(a: &OpeCode, b: &OpeCode) -> bool {
+    let erase = |_: &Register| Register::new(0);
+    a.map_registers(erase) == b.map_registers(erase)
+}
+
+/// 割り当てを調べ、誤りをすべて返す
+///
+/// 値の流れは上から順に追うので、分岐を含まないプログラムに限る
+pub fn check_allocation(opcodes: &[OpeCode], allocation: &Allocation, register_num: usize) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    for (index, opcode) in allocation.code.iter().enumerate() {
+        for reg in opcode.registers() {
+            if reg.id == 0 || reg.id > register_num {
+                violations.push(Violation::OutOfBudget { index, reg: reg.id });
+            }
+        }
+    }
+
+    check_interference(allocation, &mut violations);
+    check_values(opcodes, allocation, &mut violations);
+
+    violations
+}
+
+// 割り当て表だけを見て、同時に生きているレジスタが重なっていないか調べる
+//
+// 書き込む命令の直後に生きているレジスタと重なっていないかを見る。
+// Movの読み込み元は書き込み先と同じ値なので重なってもよい。
+// レジスタ番号は生存区間ごとに付け直したもの (`Allocation::ranges`)
+fn check_interference(allocation: &Allocation, violations: &mut Vec<Violation>) {
+    let renumbering = Renumbering::new(&allocation.ranges);
+    let ranges = renumbering.apply(&allocation.ranges);
+    let liveness = Liveness::new(&ranges);
+    let same_phys = |reg1: usize, reg2: usize| {
+        match (allocation.assignment.get(&renumbering.sparse(reg1)), allocation.assignment.get(&renumbering.sparse(reg2))) {
+            (Some(&phys1), Some(&phys2)) => phys1 == phys2,
+            _ => false,
+        }
+    };
+    let mut reported = BTreeSet::new();
+    let mut report = |original_index: usize, reg1: usize, reg2: usize| {
+        let (reg1, reg2) = (renumbering.sparse(reg1.min(reg2)), renumbering.sparse(reg1.max(reg2)));
+        if reported.insert((reg1, reg2)) {
+            let phys = allocation.assignment[&reg1];
+            violations.push(Violation::Interference { original_index, reg1, reg2, phys });
+        }
+    };
+
+    // 書き込まれずに入口から生きているレジスタどうし
+    let cfg = liveness.cfg();
+    let live: Vec<usize> = liveness.block_live_in(cfg.entry).iter().collect();
+    for (i, &reg1) in live.iter().enumerate() {
+        for &reg2 in &live[i + 1..] {
+            if same_phys(reg1, reg2) {
+                report(0, reg1, reg2);
+            }
+        }
+    }
+
+    for block in 0..cfg.blocks.len() {
+        // ブロックの中は後ろから辿るので、溜めてから前から順に報告する
+        let mut found = Vec::new();
+        liveness.for_each_live_out(block, |original_index, live| {
+            if let Some(dst) = ranges[original_index].dst() {
+                let copied = ranges[original_index].as_move().map(|(_, src)| src.id);
+                for reg in live.iter() {
+                    if reg != dst.id && Some(reg) != copied && same_phys(dst.id, reg) {
+                        found.push((original_index, dst.id, reg));
+                    }
+                }
+            }
+        });
+        found.sort_unstable();
+        for (original_index, reg1, reg2) in found {
+            report(original_index, reg1, reg2);
+        }
+    }
+}
+
+// 値の置き場所
+#[derive(Clone, Copy)]
+enum Location {
+    Phys(usize),
+    Slot(i32),
+}
+
+// 割り当て後のプログラムから消えたMov
+//
+// 読み書きするレジスタに同じ物理レジスタが割り当てられたもののほか、
+// spillしたレジスタとのMovは一時レジスタが同じ物理レジスタになるとspillのためのLoad, Storeだけが残る
+struct Elision {
+    // 残ったspillのためのコードの数
+    count: usize,
+    // 読み込み元の値の置き場所
+    from: Location,
+    // 書き込み先の値の置き場所
+    to: Location,
+}
+
+// 物理レジスタとspill用のアドレスに入っている値
+struct State {
+    phys: HashMap<usize, Value>,
+    slots: HashMap<i32, Value>,
+    // 仮想レジスタ -> 最後に書き込んだ命令の位置
+    current: HashMap<usize, usize>,
+    // spill用のアドレス
+    slot_addrs: BTreeSet<i32>,
+}
+
+impl State {
+    fn get_mut(&mut self, location: Location) -> Option<&mut Value> {
+        match location {
+            Location::Phys(phys) => self.phys.get_mut(&phys),
+            Location::Slot(addr) => self.slots.get_mut(&addr),
+        }
+    }
+
+    // spillのためのコードなら実行してtrue
+    fn spill(&mut self, index: usize, opcode: &OpeCode, violations: &mut Vec<Violation>) -> bool {
+        match *opcode {
+            OpeCode::Store { ref dst, ref src } if self.slot_addrs.contains(&dst.value) => {
+                match self.phys.get(&src.id) {
+                    Some(value) => {
+                        self.slots.insert(dst.value, Value { slot: None, ..value.clone() });
+                    },
+                    None => {
+                        self.slots.remove(&dst.value);
+                    },
+                }
+                true
+            },
+            OpeCode::Load { ref dst, ref src } if self.slot_addrs.contains(&src.value) => {
+                match self.slots.get(&src.value) {
+                    Some(value) => {
+                        self.phys.insert(dst.id, Value { slot: Some(src.value), ..value.clone() });
+                    },
+                    None => {
+                        violations.push(Violation::ReloadBeforeStore { index, addr: src.value });
+                        self.phys.remove(&dst.id);
+                    },
+                }
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // 消えたMov: 読み込み元の値が正しいか調べ、残ったspillのためのコードを実行して書き込み先の値にする
+    fn elide(&mut self, index: usize, code: &[OpeCode], original_index: usize, original: &OpeCode, elision: &Elision, violations: &mut Vec<Violation>) {
+        let (dst, src) = original.as_move().unwrap();
+        let expected = self.current.get(&src.id).cloned();
+        let holds = self.get_mut(elision.from).is_some_and(|value| value.holds(src.id, expected));
+        if !holds {
+            violations.push(match elision.from {
+                Location::Phys(phys) => Violation::Clobbered { index, reg: src.id, phys },
+                Location::Slot(addr) => Violation::WrongSlot { index, reg: src.id, addr },
+            });
+        }
+
+        for (offset, opcode) in code[index..index + elision.count].iter().enumerate() {
+            self.spill(index + offset, opcode, violations);
+        }
+
+        self.current.insert(dst.id, original_index);
+        match self.get_mut(elision.to) {
+            Some(value) if holds => value.defs.push((dst.id, original_index)),
+            _ => {
+                let value = Value::new(dst.id, original_index);
+                match elision.to {
+                    Location::Phys(phys) => self.phys.insert(phys, value),
+                    Location::Slot(addr) => self.slots.insert(addr, value),
+                };
+            },
+        }
+    }
+}
+
+// 値の流れを追い、各命令が正しい値を読んでいるか調べる
+fn check_values(opcodes: &[OpeCode], allocation: &Allocation, violations: &mut Vec<Violation>) {
+    let code = &allocation.code;
+    let slot_addrs = allocation.slots.values().cloned().collect();
+    let mut state = State { phys: HashMap::new(), slots: HashMap::new(), current: HashMap::new(), slot_addrs };
+    let mut original = opcodes.iter().enumerate().peekable();
+
+    // original_index番目の命令が、割り当て後のプログラムのindex番目から先で消えているか
+    let elided = |original_index: usize, index: usize| {
+        let (dst, src) = allocation.ranges[original_index].as_move()?;
+        let assigned = |reg: &Register| allocation.assignment.get(&reg.id).cloned();
+        let slot = |reg: &Register| allocation.slots.get(&reg.id).cloned();
+        match (assigned(dst), slot(dst), assigned(src), slot(src)) {
+            (Some(to), _, Some(from), _) if to == from => Some(Elision { count: 0, from: Location::Phys(from), to: Location::Phys(to) }),
+            // 書き込み先に直接Loadしている
+            (Some(to), _, None, Some(from)) => match code.get(index) {
+                Some(OpeCode::Load { dst, src }) if dst.id == to && src.value == from => {
+                    Some(Elision { count: 1, from: Location::Slot(from), to: Location::Phys(to) })
+                },
+                _ => None,
+            },
+            // 読み込み元から直接Storeしている
+            (None, Some(to), Some(from), _) => match code.get(index) {
+                Some(OpeCode::Store { dst, src }) if dst.value == to && src.id == from => {
+                    Some(Elision { count: 1, from: Location::Phys(from), to: Location::Slot(to) })
+                },
+                _ => None,
+            },
+            // Loadした一時レジスタからそのままStoreしている
+            (None, Some(to), None, Some(from)) => match (code.get(index), code.get(index + 1)) {
+                (Some(OpeCode::Load { dst: temp, src: addr }), Some(OpeCode::Store { dst, src }))
+                    if addr.value == from && dst.value == to && src == temp => {
+                    Some(Elision { count: 2, from: Location::Slot(from), to: Location::Slot(to) })
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    };
+
+    let mut index = 0;
+    loop {
+        // 消えたMovは後に続くspillのためのコードより先に済ませる
+        while let Some(&(original_index, original_opcode)) = original.peek() {
+            let elision = match elided(original_index, index) {
+                Some(elision) => elision,
+                None => break,
+            };
+            state.elide(index, code, original_index, original_opcode, &elision, violations);
+            index += elision.count;
+            original.next();
+        }
+
+        let opcode = match code.get(index) {
+            Some(opcode) => opcode,
+            None => break,
+        };
+
+        // spillのためのコード
+        if state.spill(index, opcode, violations) {
+            index += 1;
+            continue;
+        }
+
+        let (original_index, original_opcode) = match original.next() {
+            Some(next) if same_shape(next.1, opcode) => next,
+            _ => {
+                violations.push(Violation::Unaligned { index });
+                return;
+            },
+        };
+
+        for (reg, allocated) in original_opcode.srcs().into_iter().zip(opcode.srcs()) {
+            let expected = state.current.get(&reg.id).cloned();
+            match state.phys.get(&allocated.id) {
+                Some(value) if value.holds(reg.id, expected) => {},
+                Some(&Value { slot: Some(addr), .. }) => {
+                    violations.push(Violation::WrongSlot { index, reg: reg.id, addr });
+                },
+                _ => violations.push(Violation::Clobbered { index, reg: reg.id, phys: allocated.id }),
+            }
+        }
+
+        if let (Some(reg), Some(allocated)) = (original_opcode.dst(), opcode.dst()) {
+            state.current.insert(reg.id, original_index);
+            state.phys.insert(allocated.id, Value::new(reg.id, original_index));
+        }
+        index += 1;
+    }
+
+    if let Some((original_index, _)) = original.next() {
+        violations.push(Violation::Missing { original_index });
+    }
+}
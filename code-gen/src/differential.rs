@@ -0,0 +1,91 @@
+//! 元のプログラムと割り当て後のプログラムをVMで実行して出力を比べる
+
+use std::fmt;
+use std::ops::Range;
+
+use alloc::{AllocConfig, AllocError, Allocation, RegisterAllocator, Registry};
+use ir::{max_register_id, OpeCode};
+use vm::{run_vm, VmConfig, VmError};
+
+/// 最初に見つかった違い
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// 割り当てに失敗した
+    Alloc(AllocError),
+    /// 元のプログラムの実行に失敗した
+    Original(VmError),
+    /// 割り当て後のプログラムの実行に失敗した
+    Allocated(VmError),
+    /// 割り当て後のindex番目の命令が範囲外のレジスタを使っている
+    OverBudget { index: usize, reg: usize },
+    /// index番目のPrintの値が違う (Noneは出力がないこと)
+    Output { index: usize, expected: Option<i32>, actual: Option<i32> },
+}
+
+/// どの割り当て方法が、いくつのレジスタで違ったか
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub allocator: &'static str,
+    pub register_num: usize,
+    pub divergence: Divergence,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} --regs {}: ", self.allocator, self.register_num)?;
+
+        let show = |value: Option<i32>| value.map_or("nothing".to_string(), |value| value.to_string());
+        match self.divergence {
+            Divergence::Alloc(ref err) => write!(f, "allocation failed: {}", err),
+            Divergence::Original(ref err) => write!(f, "original program failed: {}", err),
+            Divergence::Allocated(ref err) => write!(f, "allocated program failed: {}", err),
+            Divergence::OverBudget { index, reg } => write!(f, "instruction {} uses %{}", index, reg),
+            Divergence::Output { index, expected, actual } => {
+                write!(f, "print #{} expected {} but got {}", index, show(expected), show(actual))
+            },
+        }
+    }
+}
+
+/// 1つの割り当て方法について比べ、同じなら割り当ての結果を返す
+pub fn compare(opcodes: &[OpeCode], allocator: &dyn RegisterAllocator) -> Result<Allocation, Mismatch> {
+    let register_num = allocator.config().register_num;
+    let mismatch = |divergence| Mismatch { allocator: allocator.name(), register_num, divergence };
+
+    let expected = run_vm(opcodes, &VmConfig::new(max_register_id(opcodes)))
+        .map_err(|err| mismatch(Divergence::Original(err)))?;
+    let allocation = allocator.allocate(opcodes)
+        .map_err(|err| mismatch(Divergence::Alloc(err)))?;
+
+    for (index, opcode) in allocation.code.iter().enumerate() {
+        if let Some(reg) = opcode.registers().into_iter().find(|reg| reg.id == 0 || reg.id > register_num) {
+            return Err(mismatch(Divergence::OverBudget { index, reg: reg.id }));
+        }
+    }
+    let actual = run_vm(&allocation.code, &VmConfig::new(register_num))
+        .map_err(|err| mismatch(Divergence::Allocated(err)))?;
+
+    let len = expected.output.len().max(actual.output.len());
+    for index in 0..len {
+        let expected = expected.output.get(index).cloned();
+        let actual = actual.output.get(index).cloned();
+        if expected != actual {
+            return Err(mismatch(Divergence::Output { index, expected, actual }));
+        }
+    }
+
+    Ok(allocation)
+}
+
+/// 登録されているすべての割り当て方法について、registersの範囲のレジスタ数で比べる
+pub fn compare_all(opcodes: &[OpeCode], registry: &Registry, registers: Range<usize>) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    for register_num in registers {
+        for allocator in registry.create_all(&AllocConfig::new(register_num)) {
+            if let Err(mismatch) = compare(opcodes, &*allocator) {
+                mismatches.push(mismatch);
+            }
+        }
+    }
+    mismatches
+}
@@ -0,0 +1,143 @@
+//! ランダムなプログラムで割り当て方法を試す
+
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use alloc::{AllocConfig, RegisterAllocator, Registry};
+use checker::{check_allocation, Violation};
+use differential::{compare, Divergence, Mismatch};
+use gen::{generate, GenConfig};
+use ir::{has_control_flow, OpeCode};
+use printer::Listing;
+use reduce::minimize;
+use symbolic::{equivalent, Difference};
+
+/// 失敗の理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// 割り当て中かVMの実行中にpanicした
+    Panic { allocator: &'static str, register_num: usize, message: String },
+    Mismatch(Mismatch),
+    /// 出力は同じだが割り当てが正しくない
+    Invalid { allocator: &'static str, register_num: usize, violations: Vec<Violation> },
+    /// 記号実行でPrintする式が違う
+    NotEquivalent { allocator: &'static str, register_num: usize, difference: Difference },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Failure::Panic { allocator, register_num, ref message } => {
+                write!(f, "{} --regs {}: panicked: {}", allocator, register_num, message)
+            },
+            Failure::Mismatch(ref mismatch) => write!(f, "{}", mismatch),
+            Failure::Invalid { allocator, register_num, ref violations } => {
+                write!(f, "{} --regs {}: {}", allocator, register_num, violations[0])?;
+                if violations.len() > 1 {
+                    write!(f, " (and {} more)", violations.len() - 1)?;
+                }
+                Ok(())
+            },
+            Failure::NotEquivalent { allocator, register_num, ref difference } => {
+                write!(f, "{} --regs {}: not equivalent: {}", allocator, register_num, difference)
+            },
+        }
+    }
+}
+
+impl Failure {
+    /// 失敗した割り当て方法の名前とレジスタ数
+    pub fn allocator(&self) -> (&'static str, usize) {
+        match *self {
+            Failure::Panic { allocator, register_num, .. } => (allocator, register_num),
+            Failure::Mismatch(ref mismatch) => (mismatch.allocator, mismatch.register_num),
+            Failure::Invalid { allocator, register_num, .. }
+            | Failure::NotEquivalent { allocator, register_num, .. } => (allocator, register_num),
+        }
+    }
+
+    /// 同じ種類の失敗か (値やメッセージは比べない)
+    pub fn same_kind(&self, other: &Failure) -> bool {
+        match (self, other) {
+            (Failure::Panic { .. }, Failure::Panic { .. })
+            | (Failure::Invalid { .. }, Failure::Invalid { .. })
+            | (Failure::NotEquivalent { .. }, Failure::NotEquivalent { .. }) => true,
+            (Failure::Mismatch(a), Failure::Mismatch(b)) => {
+                match (&a.divergence, &b.divergence) {
+                    (Divergence::Alloc(a), Divergence::Alloc(b)) => mem::discriminant(a) == mem::discriminant(b),
+                    (a, b) => mem::discriminant(a) == mem::discriminant(b),
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+/// 失敗したプログラム
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzFailure {
+    pub seed: u64,
+    pub opcodes: Vec<OpeCode>,
+    pub failure: Failure,
+}
+
+/// 1つのプログラムを1つの割り当て方法で試す
+///
+/// 出力を比べたあと、`checker`で割り当てを調べ、`symbolic`で等価性を調べる
+/// (この2つは直線のコードしか扱えないので、分岐を含むプログラムでは出力だけを比べる)
+pub fn check(opcodes: &[OpeCode], allocator: &dyn RegisterAllocator) -> Result<(), Failure> {
+    let register_num = allocator.config().register_num;
+    match panic::catch_unwind(AssertUnwindSafe(|| compare(opcodes, allocator))) {
+        Ok(Ok(_)) if has_control_flow(opcodes) => Ok(()),
+        Ok(Ok(allocation)) => {
+            let violations = check_allocation(opcodes, &allocation, register_num);
+            if !violations.is_empty() {
+                return Err(Failure::Invalid { allocator: allocator.name(), register_num, violations });
+            }
+            equivalent(opcodes, &allocation.code)
+                .map_err(|difference| Failure::NotEquivalent { allocator: allocator.name(), register_num, difference })
+        },
+        Ok(Err(mismatch)) => Err(Failure::Mismatch(mismatch)),
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(Failure::Panic { allocator: allocator.name(), register_num, message })
+        },
+    }
+}
+
+/// seedsの各シードでプログラムを作り、すべての割り当て方法とレジスタ数で試す
+///
+/// プログラムごとに最初の失敗を返す
+pub fn fuzz(seeds: Range<u64>, config: &GenConfig, registry: &Registry, registers: Range<usize>) -> Vec<FuzzFailure> {
+    let allocators: Vec<Box<dyn RegisterAllocator>> = registers
+        .flat_map(|register_num| registry.create_all(&AllocConfig::new(register_num)))
+        .collect();
+
+    let mut failures = Vec::new();
+    for seed in seeds {
+        let opcodes = generate(seed, config);
+        if let Some(failure) = allocators.iter().filter_map(|allocator| check(&opcodes, &**allocator).err()).next() {
+            failures.push(FuzzFailure { seed, opcodes, failure });
+        }
+    }
+    failures
+}
+
+/// failureと同じ種類の失敗が起きる最小のプログラムを探す
+pub fn reduce(opcodes: &[OpeCode], allocator: &dyn RegisterAllocator, failure: &Failure) -> Vec<OpeCode> {
+    minimize(opcodes, |candidate| {
+        check(candidate, allocator).err().is_some_and(|found| found.same_kind(failure))
+    })
+}
+
+/// 再現用の.sファイルの内容
+///
+/// 先頭のコメントに割り当て方法とレジスタ数を書く
+pub fn repro(opcodes: &[OpeCode], failure: &Failure) -> String {
+    let (allocator, register_num) = failure.allocator();
+    format!("; algo: {}\n; regs: {}\n; {}\n\n{}", allocator, register_num, failure, Listing::new(opcodes))
+}
@@ -0,0 +1,189 @@
+//! シードからランダムなプログラムを作る
+
+use ir::{Integer, OpeCode, Register};
+
+/// xorshift64*
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// 状態が0だと0しか出ないので、シードをsplitmix64で混ぜ、0なら別の値にする
+    pub fn new(seed: u64) -> Rng {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        Rng { state: if z == 0 { 0x9e37_79b9_7f4a_7c15 } else { z } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// 0からn - 1まで
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// 各命令の出やすさ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mix {
+    pub ldi: u32,
+    pub add: u32,
+    pub store: u32,
+    pub load: u32,
+    pub print: u32,
+    pub mov: u32,
+}
+
+impl Default for Mix {
+    fn default() -> Mix {
+        Mix { ldi: 3, add: 4, store: 1, load: 1, print: 2, mov: 1 }
+    }
+}
+
+/// 生成するプログラムの形
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenConfig {
+    /// 命令数
+    pub length: usize,
+    /// 使うレジスタ番号は%1から%registersまで
+    pub registers: usize,
+    /// 同時に生きている値の最大数
+    pub live_values: usize,
+    /// Store, Loadに使うアドレスは0からaddresses - 1まで
+    pub addresses: usize,
+    pub mix: Mix,
+}
+
+impl Default for GenConfig {
+    fn default() -> GenConfig {
+        GenConfig {
+            length: 40,
+            registers: 16,
+            live_values: 8,
+            addresses: 4,
+            mix: Mix::default(),
+        }
+    }
+}
+
+enum Kind {
+    LdI, Add, Store, Load, Print, Mov,
+}
+
+struct Generator<'a> {
+    config: &'a GenConfig,
+    rng: Rng,
+    // 生きている値を持つレジスタ
+    live: Vec<usize>,
+}
+
+impl<'a> Generator<'a> {
+    fn kind(&mut self) -> Kind {
+        let mix = &self.config.mix;
+        // 生きている値がなければ読む命令は作れない
+        let weights = if self.live.is_empty() {
+            [mix.ldi, 0, 0, mix.load, 0, 0]
+        } else {
+            [mix.ldi, mix.add, mix.store, mix.load, mix.print, mix.mov]
+        };
+        let total: u32 = weights.iter().sum();
+        if total == 0 {
+            return Kind::LdI;
+        }
+
+        let mut n = self.rng.below(total as usize) as u32;
+        for (i, &weight) in weights.iter().enumerate() {
+            if n < weight {
+                return match i {
+                    0 => Kind::LdI,
+                    1 => Kind::Add,
+                    2 => Kind::Store,
+                    3 => Kind::Load,
+                    4 => Kind::Print,
+                    _ => Kind::Mov,
+                };
+            }
+            n -= weight;
+        }
+        unreachable!()
+    }
+
+    fn src(&mut self) -> Register {
+        let i = self.rng.below(self.live.len());
+        Register::new(self.live[i])
+    }
+
+    // 生きていないレジスタに書き込み、溢れた値は殺す
+    fn dst(&mut self) -> Register {
+        let registers = self.config.registers.max(1);
+        let candidates: Vec<usize> = (1..registers + 1).filter(|id| !self.live.contains(id)).collect();
+        let id = if candidates.is_empty() {
+            let i = self.rng.below(self.live.len());
+            self.live.remove(i)
+        } else {
+            candidates[self.rng.below(candidates.len())]
+        };
+
+        self.live.push(id);
+        if self.live.len() > self.config.live_values.max(1) {
+            let i = self.rng.below(self.live.len() - 1);
+            self.live.remove(i);
+        }
+        Register::new(id)
+    }
+
+    fn addr(&mut self) -> Integer {
+        Integer::new(self.rng.below(self.config.addresses.max(1)) as i32)
+    }
+
+    fn value(&mut self) -> Integer {
+        Integer::new(self.rng.below(201) as i32 - 100)
+    }
+
+    fn opcode(&mut self) -> OpeCode {
+        match self.kind() {
+            Kind::LdI => {
+                let value = self.value();
+                OpeCode::LdI { dst: self.dst(), value }
+            },
+            Kind::Add => {
+                let src1 = self.src();
+                let src2 = self.src();
+                OpeCode::Add { dst: self.dst(), src1, src2 }
+            },
+            Kind::Store => {
+                let src = self.src();
+                OpeCode::Store { dst: self.addr(), src }
+            },
+            Kind::Load => {
+                let src = self.addr();
+                OpeCode::Load { dst: self.dst(), src }
+            },
+            Kind::Print => OpeCode::Print { src: self.src() },
+            Kind::Mov => {
+                let src = self.src();
+                OpeCode::Mov { dst: self.dst(), src }
+            },
+        }
+    }
+}
+
+/// 同じseedとconfigからは同じプログラムができる
+///
+/// 読むレジスタは必ずその前に書き込まれている
+pub fn generate(seed: u64, config: &GenConfig) -> Vec<OpeCode> {
+    let mut generator = Generator {
+        config,
+        rng: Rng::new(seed),
+        live: Vec::new(),
+    };
+    (0..config.length).map(|_| generator.opcode()).collect()
+}
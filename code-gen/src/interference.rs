@@ -0,0 +1,184 @@
+//! 干渉グラフ
+//!
+//! 辺の有無は三角行列のビット集合で、隣接ノードはリストで持つ。
+//! ノードを取り除いたときは隣接ノードの次数だけを減らすので、簡約の各段階でグラフを作り直さなくてよい
+
+use std::collections::HashSet;
+
+use bitset::BitSet;
+use cfg::Cfg;
+use ir::{max_register_id, OpeCode};
+use liveness::block_liveness;
+
+// 三角行列のビット数がこれを超えるときはハッシュ集合で辺を持つ
+const MAX_MATRIX_BITS: usize = 1 << 27;
+
+enum Edges {
+    // (a, b) (a > b) はa * (a - 1) / 2 + b番目のビット
+    Matrix(BitSet),
+    Hashed(HashSet<(usize, usize)>),
+}
+
+/// 干渉グラフ (ノードはレジスタ番号)
+pub struct InterferenceGraph {
+    edges: Edges,
+    adjacency: Vec<Vec<usize>>,
+    // 取り除かれていない隣接ノードの数
+    degree: Vec<usize>,
+    removed: BitSet,
+}
+
+impl InterferenceGraph {
+    /// 0からlen - 1までのノードを持つ、辺のないグラフ
+    pub fn new(len: usize) -> InterferenceGraph {
+        let bits = len * len.saturating_sub(1) / 2;
+        let edges = if bits <= MAX_MATRIX_BITS {
+            Edges::Matrix(BitSet::new(bits))
+        } else {
+            Edges::Hashed(HashSet::new())
+        };
+        InterferenceGraph {
+            edges,
+            adjacency: vec![Vec::new(); len],
+            degree: vec![0; len],
+            removed: BitSet::new(len),
+        }
+    }
+
+    /// 後ろ向きに1回走査して作る
+    ///
+    /// 各命令で書き込むレジスタと、その直後に生きているレジスタの間に辺を張る。
+    /// Movの書き込み先と読み込み元は同じ値を持つので、その命令では辺を張らない。
+    /// excludedのノードには辺を張らない
+    pub fn build(opcodes: &[OpeCode], excluded: &BitSet) -> InterferenceGraph {
+        let len = max_register_id(opcodes) + 1;
+        let cfg = Cfg::new(opcodes);
+        let (block_in, block_out) = block_liveness(&cfg, len);
+        let mut graph = InterferenceGraph::new(len);
+
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            let mut live = block_out[id].clone();
+            live.difference_with(excluded);
+            for opcode in block.code.iter().rev() {
+                if let Some(dst) = opcode.dst() {
+                    live.remove(dst.id);
+                    if !excluded.contains(dst.id) {
+                        let copied = opcode.as_move().map(|(_, src)| src.id);
+                        for reg_id in live.iter().filter(|&reg_id| Some(reg_id) != copied) {
+                            graph.add_edge(dst.id, reg_id);
+                        }
+                    }
+                }
+                for src in opcode.srcs() {
+                    if !excluded.contains(src.id) {
+                        live.insert(src.id);
+                    }
+                }
+            }
+        }
+
+        // 入口 (と到達できないブロック) で生きている値は書き込みを通らずに届くので、互いに干渉させる
+        let mut reachable = BitSet::new(cfg.blocks.len());
+        for id in cfg.postorder() {
+            reachable.insert(id);
+        }
+        for (id, live) in block_in.iter().enumerate() {
+            if id != cfg.entry && reachable.contains(id) {
+                continue;
+            }
+            let mut live = live.clone();
+            live.difference_with(excluded);
+            let live: Vec<usize> = live.iter().collect();
+            for (i, &a) in live.iter().enumerate() {
+                for &b in &live[i + 1..] {
+                    graph.add_edge(a, b);
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// ノードの数
+    pub fn len(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adjacency.is_empty()
+    }
+
+    fn key(a: usize, b: usize) -> (usize, usize) {
+        if a > b { (a, b) } else { (b, a) }
+    }
+
+    /// 辺を足す。新しい辺ならtrue
+    ///
+    /// 取り除いたノードには足せない
+    pub fn add_edge(&mut self, a: usize, b: usize) -> bool {
+        if a == b {
+            return false;
+        }
+        assert!(!self.removed.contains(a) && !self.removed.contains(b), "adding an edge to a removed node");
+        let (hi, lo) = InterferenceGraph::key(a, b);
+        let added = match self.edges {
+            Edges::Matrix(ref mut bits) => bits.insert(hi * (hi - 1) / 2 + lo),
+            Edges::Hashed(ref mut set) => set.insert((hi, lo)),
+        };
+        if added {
+            self.adjacency[a].push(b);
+            self.adjacency[b].push(a);
+            self.degree[a] += 1;
+            self.degree[b] += 1;
+        }
+        added
+    }
+
+    /// aとbが干渉するか (取り除いたノードの辺も含む)
+    pub fn interferes(&self, a: usize, b: usize) -> bool {
+        if a == b {
+            return false;
+        }
+        let (hi, lo) = InterferenceGraph::key(a, b);
+        match self.edges {
+            Edges::Matrix(ref bits) => bits.contains(hi * (hi - 1) / 2 + lo),
+            Edges::Hashed(ref set) => set.contains(&(hi, lo)),
+        }
+    }
+
+    /// 隣接ノード (取り除いたノードも含む)
+    pub fn neighbors(&self, node: usize) -> &[usize] {
+        &self.adjacency[node]
+    }
+
+    /// 取り除かれていない隣接ノードの数
+    pub fn degree(&self, node: usize) -> usize {
+        self.degree[node]
+    }
+
+    /// 辺の数
+    pub fn edge_count(&self) -> usize {
+        self.adjacency.iter().map(|adjacency| adjacency.len()).sum::<usize>() / 2
+    }
+
+    /// ノードを取り除き、隣接ノードの次数を減らす
+    ///
+    /// 次数が変わったノードを返す
+    pub fn remove(&mut self, node: usize) -> Vec<usize> {
+        if !self.removed.insert(node) {
+            return Vec::new();
+        }
+        let mut changed = Vec::new();
+        for &neighbor in &self.adjacency[node] {
+            if !self.removed.contains(neighbor) {
+                self.degree[neighbor] -= 1;
+                changed.push(neighbor);
+            }
+        }
+        changed
+    }
+
+    pub fn is_removed(&self, node: usize) -> bool {
+        self.removed.contains(node)
+    }
+}
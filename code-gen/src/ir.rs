@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// 仮想レジスタまたは物理レジスタ
+#[derive(Clone, PartialEq)]
+pub struct Register {
+    pub id: usize,     // 1 base
+    pub size: usize,
+}
+
+impl Register {
+    pub fn new(id: usize) -> Register {
+        Register {
+            id,
+            size: 32,
+        }
+    }
+}
+
+impl fmt::Debug for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.id)
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.id)
+    }
+}
+
+/// 即値またはメモリのアドレス
+#[derive(Clone, PartialEq)]
+pub struct Integer {
+    pub value: i32,
+}
+
+impl Integer {
+    pub fn new(value: i32) -> Integer {
+        Integer {
+            value
+        }
+    }
+}
+
+impl fmt::Debug for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl fmt::Display for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+/// 分岐先のラベル
+#[derive(Clone, PartialEq)]
+pub struct Label {
+    pub name: String,
+}
+
+impl Label {
+    pub fn new<S: Into<String>>(name: S) -> Label {
+        Label {
+            name: name.into()
+        }
+    }
+}
+
+impl fmt::Debug for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// 命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpeCode {
+    Add { dst: Register, src1: Register, src2: Register },
+    LdI { dst: Register, value: Integer },
+    Store { dst: Integer, src: Register },
+    Load { dst: Register, src: Integer },
+    Print { src: Register },
+    /// レジスタ間のコピー
+    Mov { dst: Register, src: Register },
+    /// 分岐先 (何も実行しない)
+    Label { label: Label },
+    Jmp { target: Label },
+    /// src1 == src2なら分岐する
+    Beq { src1: Register, src2: Register, target: Label },
+    /// src1 != src2なら分岐する
+    Bne { src1: Register, src2: Register, target: Label },
+    /// src1 < src2なら分岐する (符号付き)
+    Blt { src1: Register, src2: Register, target: Label },
+}
+
+// parser::parseで読める形式で出力する
+impl fmt::Display for OpeCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OpeCode::Add { ref dst, ref src1, ref src2 } => write!(f, "add {}, {}, {}", dst, src1, src2),
+            OpeCode::LdI { ref dst, ref value } => write!(f, "loadi {}, {}", dst, value),
+            OpeCode::Store { ref dst, ref src } => write!(f, "store {}, {}", dst, src),
+            OpeCode::Load { ref dst, ref src } => write!(f, "load {}, {}", dst, src),
+            OpeCode::Print { ref src } => write!(f, "print {}", src),
+            OpeCode::Mov { ref dst, ref src } => write!(f, "mov {}, {}", dst, src),
+            OpeCode::Label { ref label } => write!(f, "{}:", label),
+            OpeCode::Jmp { ref target } => write!(f, "jmp {}", target),
+            OpeCode::Beq { ref src1, ref src2, ref target } => write!(f, "beq {}, {}, {}", src1, src2, target),
+            OpeCode::Bne { ref src1, ref src2, ref target } => write!(f, "bne {}, {}, {}", src1, src2, target),
+            OpeCode::Blt { ref src1, ref src2, ref target } => write!(f, "blt {}, {}, {}", src1, src2, target),
+        }
+    }
+}
+
+impl OpeCode {
+    /// 書き込むレジスタ
+    pub fn dst(&self) -> Option<&Register> {
+        match *self {
+            OpeCode::Add { ref dst, .. }
+            | OpeCode::LdI { ref dst, .. }
+            | OpeCode::Load { ref dst, .. }
+            | OpeCode::Mov { ref dst, .. } => Some(dst),
+            _ => None,
+        }
+    }
+
+    /// 読み込むレジスタ
+    pub fn srcs(&self) -> Vec<&Register> {
+        match *self {
+            OpeCode::Add { ref src1, ref src2, .. }
+            | OpeCode::Beq { ref src1, ref src2, .. }
+            | OpeCode::Bne { ref src1, ref src2, .. }
+            | OpeCode::Blt { ref src1, ref src2, .. } => vec![src1, src2],
+            OpeCode::Store { ref src, .. } | OpeCode::Print { ref src } | OpeCode::Mov { ref src, .. } => vec![src],
+            OpeCode::LdI { .. } | OpeCode::Load { .. } | OpeCode::Label { .. } | OpeCode::Jmp { .. } => vec![],
+        }
+    }
+
+    /// 読み書きするすべてのレジスタ
+    pub fn registers(&self) -> Vec<&Register> {
+        let mut registers = self.srcs();
+        registers.extend(self.dst());
+        registers
+    }
+
+    /// すべてのレジスタをfで置き換えた命令
+    pub fn map_registers<F: FnMut(&Register) -> Register>(&self, mut f: F) -> OpeCode {
+        match *self {
+            OpeCode::Add { ref dst, ref src1, ref src2 } => OpeCode::Add { dst: f(dst), src1: f(src1), src2: f(src2) },
+            OpeCode::LdI { ref dst, ref value } => OpeCode::LdI { dst: f(dst), value: value.clone() },
+            OpeCode::Store { ref dst, ref src } => OpeCode::Store { dst: dst.clone(), src: f(src) },
+            OpeCode::Load { ref dst, ref src } => OpeCode::Load { dst: f(dst), src: src.clone() },
+            OpeCode::Print { ref src } => OpeCode::Print { src: f(src) },
+            OpeCode::Mov { ref dst, ref src } => OpeCode::Mov { dst: f(dst), src: f(src) },
+            OpeCode::Label { .. } | OpeCode::Jmp { .. } => self.clone(),
+            OpeCode::Beq { ref src1, ref src2, ref target } => OpeCode::Beq { src1: f(src1), src2: f(src2), target: target.clone() },
+            OpeCode::Bne { ref src1, ref src2, ref target } => OpeCode::Bne { src1: f(src1), src2: f(src2), target: target.clone() },
+            OpeCode::Blt { ref src1, ref src2, ref target } => OpeCode::Blt { src1: f(src1), src2: f(src2), target: target.clone() },
+        }
+    }
+
+    /// 読むレジスタをsrc (`srcs`の順に呼ぶ)、書き込むレジスタをdstで置き換えた命令
+    ///
+    /// `add %5, %5, %3`のように同じレジスタを読み書きする命令で、読む側と書く側を区別したいときに使う
+    pub fn map_operands<F, G>(&self, mut src: F, mut dst: G) -> OpeCode
+        where F: FnMut(&Register) -> Register, G: FnMut(&Register) -> Register
+    {
+        match *self {
+            OpeCode::Add { dst: ref d, ref src1, ref src2 } => {
+                let src1 = src(src1);
+                let src2 = src(src2);
+                OpeCode::Add { dst: dst(d), src1, src2 }
+            },
+            OpeCode::LdI { dst: ref d, ref value } => OpeCode::LdI { dst: dst(d), value: value.clone() },
+            OpeCode::Load { dst: ref d, src: ref addr } => OpeCode::Load { dst: dst(d), src: addr.clone() },
+            OpeCode::Mov { dst: ref d, src: ref s } => {
+                let s = src(s);
+                OpeCode::Mov { dst: dst(d), src: s }
+            },
+            _ => self.map_registers(src),
+        }
+    }
+
+    /// コピーなら (書き込むレジスタ, 読むレジスタ)
+    pub fn as_move(&self) -> Option<(&Register, &Register)> {
+        match *self {
+            OpeCode::Mov { ref dst, ref src } => Some((dst, src)),
+            _ => None,
+        }
+    }
+
+    /// 分岐先
+    pub fn target(&self) -> Option<&Label> {
+        match *self {
+            OpeCode::Jmp { ref target }
+            | OpeCode::Beq { ref target, .. }
+            | OpeCode::Bne { ref target, .. }
+            | OpeCode::Blt { ref target, .. } => Some(target),
+            _ => None,
+        }
+    }
+
+    /// 次の命令に進むことがあるか
+    pub fn falls_through(&self) -> bool {
+        !matches!(*self, OpeCode::Jmp { .. })
+    }
+
+    /// ラベルか分岐命令か
+    pub fn is_control_flow(&self) -> bool {
+        matches!(*self, OpeCode::Label { .. }) || self.target().is_some()
+    }
+}
+
+// trait OpeCode {
+//     fn to_string(&self) -> String;
+// }
+//
+// macro_rules! def_opecode {
+//     ($opname:ident, $($name:ident : $t:ident)*) => {
+//         struct $opname {
+//             $(
+//                 $name: $t,
+//             )*
+//         }
+//
+//         impl $opname {
+//             pub fn new($( $name: $t, )*) -> $opname {
+//                 $opname {
+//                     $( $name: $name, )*
+//                 }
+//             }
+//         }
+//
+//         impl OpeCode for $opname {
+//             fn to_string(&self) -> String {
+//                 format!("$opname {}, {}, {}", $(self.$name.to_string(),)*)
+//             }
+//         }
+//     };
+// }
+//
+// // ここではマクロは使えない?
+// // def_opecode![Add, dst: Register, src1: Register, src2: Register];
+// // def_opecode![LdI, dst: Register, value: Integer];
+//
+
+/// ラベル名 -> ラベルの位置
+pub fn label_positions(opcodes: &[OpeCode]) -> HashMap<&str, usize> {
+    opcodes.iter().enumerate().filter_map(|(index, op)| match *op {
+        OpeCode::Label { ref label } => Some((label.name.as_str(), index)),
+        _ => None,
+    }).collect()
+}
+
+/// ラベルか分岐命令を含むか
+pub fn has_control_flow(opcodes: &[OpeCode]) -> bool {
+    opcodes.iter().any(OpeCode::is_control_flow)
+}
+
+/// プログラム中で使われている最大のレジスタ番号 (空なら0)
+pub fn max_register_id(opcodes: &[OpeCode]) -> usize {
+    opcodes.iter().flat_map(|op| op.registers()).map(|reg| reg.id).max().unwrap_or(0)
+}
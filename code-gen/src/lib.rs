@@ -0,0 +1,43 @@
+//! 仮想レジスタを使うアセンブリのレジスタ割り当ての練習
+//!
+//! - `ir`: 命令 (`OpeCode`) とオペランド
+//! - `parser`, `printer`: `.s`形式との相互変換
+//! - `cfg`: 基本ブロックと制御フローグラフ
+//! - `bitset`, `liveness`: データフロー解析による生存区間解析
+//! - `interference`: 干渉グラフ
+//! - `renumber`, `webs`: 割り当ての前にレジスタ番号を付け直す
+//! - `alloc`: レジスタ割り当て
+//! - `vm`: 命令を実行するVM
+//! - `differential`: 割り当て前後のプログラムの出力の比較
+//! - `checker`: 割り当ての静的な検査
+//! - `symbolic`: 記号実行による割り当て前後のプログラムの等価性の検査
+//! - `gen`, `fuzz`: ランダムなプログラムによるテスト
+//! - `reduce`: 失敗するプログラムの最小化
+
+macro_rules! reg {
+    ($id:expr) => {
+        Register::new($id)
+    };
+}
+
+pub mod alloc;
+pub mod bitset;
+pub mod cfg;
+pub mod checker;
+pub mod differential;
+pub mod fuzz;
+pub mod gen;
+pub mod interference;
+pub mod ir;
+pub mod liveness;
+pub mod parser;
+pub mod printer;
+pub mod reduce;
+pub mod renumber;
+pub mod symbolic;
+pub mod vm;
+pub mod webs;
+
+pub use alloc::{allocate_registers1, allocate_registers2, allocate_registers_coalescing, allocate_registers_linear_scan, AllocError};
+pub use ir::{Integer, OpeCode, Register};
+pub use vm::run_vm;
@@ -0,0 +1,192 @@
+//! 生存区間解析
+//!
+//! 制御フローグラフの上で後ろ向きのデータフロー解析を収束するまで繰り返し、
+//! ブロックごとに生きているレジスタをビット集合で求める
+
+use bitset::BitSet;
+use cfg::Cfg;
+use ir::{max_register_id, OpeCode};
+
+/// 解析の結果
+///
+/// 命令の位置 (point) は元のプログラムでの位置。
+/// 命令ごとの集合は持たず、問い合わせのたびにブロックの出口から後ろ向きに辿って求める
+pub struct Liveness {
+    cfg: Cfg,
+    // ブロックの先頭の命令の位置
+    block_start: Vec<usize>,
+    block_in: Vec<BitSet>,
+    block_out: Vec<BitSet>,
+    // 命令が書き込むレジスタ
+    defs: Vec<Option<usize>>,
+}
+
+// 読むレジスタを足し、書き込むレジスタを除く
+fn transfer(opcode: &OpeCode, live: &mut BitSet) {
+    if let Some(dst) = opcode.dst() {
+        live.remove(dst.id);
+    }
+    for src in opcode.srcs() {
+        live.insert(src.id);
+    }
+}
+
+/// ブロックの入口と出口で生きているレジスタ
+///
+/// universeはレジスタ番号の上限 (含まない)。命令ごとの集合がいらないときはこちらを使う
+pub fn block_liveness(cfg: &Cfg, universe: usize) -> (Vec<BitSet>, Vec<BitSet>) {
+    let block_num = cfg.blocks.len();
+
+    // 到達できないブロックも解析する
+    let mut order = cfg.postorder();
+    let mut visited = BitSet::new(block_num);
+    for &id in &order {
+        visited.insert(id);
+    }
+    order.extend((0..block_num).filter(|&id| !visited.contains(id)));
+
+    let mut block_in = vec![BitSet::new(universe); block_num];
+    let mut block_out = vec![BitSet::new(universe); block_num];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &id in &order {
+            let mut out = BitSet::new(universe);
+            for &succ in &cfg.blocks[id].succs {
+                out.union_with(&block_in[succ]);
+            }
+            let mut live = out.clone();
+            for opcode in cfg.blocks[id].code.iter().rev() {
+                transfer(opcode, &mut live);
+            }
+            block_out[id] = out;
+            if live != block_in[id] {
+                block_in[id] = live;
+                changed = true;
+            }
+        }
+    }
+
+    (block_in, block_out)
+}
+
+impl Liveness {
+    pub fn new(opcodes: &[OpeCode]) -> Liveness {
+        let cfg = Cfg::new(opcodes);
+        let universe = max_register_id(opcodes) + 1;
+        let block_num = cfg.blocks.len();
+
+        // Cfg::newはプログラムの順にブロックを並べる
+        let mut block_start = Vec::with_capacity(block_num);
+        let mut start = 0;
+        for block in &cfg.blocks {
+            block_start.push(start);
+            start += block.code.len();
+        }
+
+        let (block_in, block_out) = block_liveness(&cfg, universe);
+        let defs = opcodes.iter().map(|op| op.dst().map(|reg| reg.id)).collect();
+
+        Liveness { cfg, block_start, block_in, block_out, defs }
+    }
+
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    /// ブロックの先頭の命令の位置
+    pub fn block_start(&self, block: usize) -> usize {
+        self.block_start[block]
+    }
+
+    /// ブロックの入口で生きているレジスタ
+    pub fn block_live_in(&self, block: usize) -> &BitSet {
+        &self.block_in[block]
+    }
+
+    /// ブロックの出口で生きているレジスタ
+    pub fn block_live_out(&self, block: usize) -> &BitSet {
+        &self.block_out[block]
+    }
+
+    // pointの命令を含むブロック
+    fn block_of(&self, point: usize) -> usize {
+        // 空のブロックは次のブロックと先頭の位置が同じ
+        let mut id = self.block_start.partition_point(|&start| start <= point) - 1;
+        while self.cfg.blocks[id].code.is_empty() {
+            id -= 1;
+        }
+        id
+    }
+
+    /// blockの命令を後ろから順に、その命令の位置と直後で生きているレジスタをfに渡す
+    pub fn for_each_live_out<F: FnMut(usize, &BitSet)>(&self, block: usize, mut f: F) {
+        let mut live = self.block_out[block].clone();
+        for (offset, opcode) in self.cfg.blocks[block].code.iter().enumerate().rev() {
+            f(self.block_start[block] + offset, &live);
+            transfer(opcode, &mut live);
+        }
+    }
+
+    /// pointの命令の直後で生きているレジスタ
+    ///
+    /// ブロックの出口から辿るので、ブロックの長さに比例する時間がかかる
+    pub fn live_out(&self, point: usize) -> BitSet {
+        let block = self.block_of(point);
+        let start = self.block_start[block];
+        let mut live = self.block_out[block].clone();
+        for opcode in self.cfg.blocks[block].code[point - start + 1..].iter().rev() {
+            transfer(opcode, &mut live);
+        }
+        live
+    }
+
+    /// pointの命令の直前で生きているレジスタ
+    pub fn live_in(&self, point: usize) -> BitSet {
+        let mut live = self.live_out(point);
+        transfer(self.opcode(point), &mut live);
+        live
+    }
+
+    fn opcode(&self, point: usize) -> &OpeCode {
+        let block = self.block_of(point);
+        &self.cfg.blocks[block].code[point - self.block_start[block]]
+    }
+
+    /// pointの命令の実行中にregが物理レジスタを占めているか
+    ///
+    /// pointで書き込まれるか、pointより後で読まれる。pointで最後に読まれるレジスタは含まない
+    pub fn is_live_at(&self, reg: usize, point: usize) -> bool {
+        self.defs[point] == Some(reg) || self.live_out(point).contains(reg)
+    }
+
+    /// pointの命令の実行中に物理レジスタを占めているレジスタ (`is_live_at`を満たすもの)
+    pub fn live_at(&self, point: usize) -> BitSet {
+        let mut live = self.live_out(point);
+        if let Some(reg) = self.defs[point] {
+            live.insert(reg);
+        }
+        live
+    }
+
+    /// pointの命令をまたいで生きているレジスタ (直前と直後の両方で生きていて、pointで書き込まれない)
+    pub fn live_across(&self, point: usize) -> BitSet {
+        let live_out = self.live_out(point);
+        let mut live = live_out.clone();
+        transfer(self.opcode(point), &mut live);
+        live.intersect_with(&live_out);
+        if let Some(reg) = self.defs[point] {
+            live.remove(reg);
+        }
+        live
+    }
+
+    /// 命令の数
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+}
@@ -1,631 +1,304 @@
-use std::collections::HashMap;
-use std::fmt;
-
-trait Operand {
-}
-
-#[derive(Clone)]
-struct Register {
-    id: usize,     // 1 base
-    size: usize,
-}
-
-impl Register {
-    fn new(id: usize) -> Register {
-        Register {
-            id: id,
-            size: 32,
-        }
-    }
-}
-
-impl Operand for Register {}
+extern crate compiler_practice;
 
-impl fmt::Debug for Register {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "%{}", self.id)
-    }
-}
-
-#[derive(Clone)]
-struct Integer {
-    value: i32,
-}
-
-impl Integer {
-    fn new(value: i32) -> Integer {
-        Integer {
-            value: value
-        }
-    }
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::panic;
+use std::path::Path;
+use std::process;
+
+use compiler_practice::ir::max_register_id;
+use compiler_practice::alloc::{AllocConfig, Registry, MIN_REGISTER_NUM};
+use compiler_practice::vm::{run_vm_with_sink, Overflow, Stdout, VmConfig};
+use compiler_practice::gen::GenConfig;
+use compiler_practice::{fuzz, parser, printer, OpeCode};
+
+const USAGE: &str = "usage:
+    compiler-practice alloc --algo NAME --regs N [--spill-costs] [-o OUT] IN
+    compiler-practice run [--regs N] [--mem N] [--overflow wrapping|checked|saturating] [--steps N] [--dump] IN
+    compiler-practice bench [--regs A..B] IN
+    compiler-practice check [--regs A..B] IN
+    compiler-practice fuzz [--seed S] [--iterations N] [--len N] [--live N] [--regs A..B] [-o DIR]
+    compiler-practice reduce --algo NAME --regs N [-o OUT] IN";
+
+enum Command {
+    Alloc { algo: String, register_num: usize, spill_costs: bool, input: String, output: Option<String> },
+    Run { register_num: Option<usize>, memory_size: Option<usize>, overflow: Overflow, max_steps: Option<usize>, dump: bool, input: String },
+    Bench { registers: Range<usize>, input: String },
+    Check { registers: Range<usize>, input: String },
+    Fuzz { seeds: Range<u64>, config: GenConfig, registers: Range<usize>, output: Option<String> },
+    Reduce { algo: String, register_num: usize, input: String, output: Option<String> },
 }
 
-impl Operand for Integer {}
-
-impl fmt::Debug for Integer {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.value)
-    }
+// Usageは終了コード2, Failureは1
+enum CliError {
+    Usage(String),
+    Failure(String),
 }
 
-#[derive(Debug, Clone)]
-enum OpeCode {
-    Add { dst: Register, src1: Register, src2: Register },
-    LdI { dst: Register, value: Integer },
-    Store { dst: Integer, src: Register },
-    Load { dst: Register, src: Integer },
-    Print { src: Register },
+fn parse_register_num(arg: &str) -> Result<usize, CliError> {
+    arg.parse().map_err(|_| CliError::Usage(format!("bad register number `{}`", arg)))
 }
 
-impl OpeCode {
-    fn to_string(&self) -> String {
-        format!("{:?}", self)
+// "A..B" (Bは含まない) または "N"
+fn parse_register_range(arg: &str) -> Result<Range<usize>, CliError> {
+    match arg.find("..") {
+        Some(pos) => {
+            let range = parse_register_num(&arg[..pos])?..parse_register_num(&arg[pos + 2..])?;
+            if range.start >= range.end {
+                return Err(CliError::Usage(format!("empty register range `{}`", arg)));
+            }
+            Ok(range)
+        },
+        None => {
+            let n = parse_register_num(arg)?;
+            Ok(n..n + 1)
+        },
     }
 }
 
-// trait OpeCode {
-//     fn to_string(&self) -> String;
-// }
-//
-// macro_rules! def_opecode {
-//     ($opname:ident, $($name:ident : $t:ident)*) => {
-//         struct $opname {
-//             $(
-//                 $name: $t,
-//             )*
-//         }
-//
-//         impl $opname {
-//             fn new($( $name: $t, )*) -> $opname {
-//                 $opname {
-//                     $( $name: $name, )*
-//                 }
-//             }
-//         }
-//
-//         impl OpeCode for $opname {
-//             fn to_string(&self) -> String {
-//                 format!("$opname {}, {}, {}", $(self.$name.to_string(),)*)
-//             }
-//         }
-//     };
-// }
-//
-// // ここではマクロは使えない?
-// // def_opecode![Add, dst: Register, src1: Register, src2: Register];
-// // def_opecode![LdI, dst: Register, value: Integer];
-//
-
-macro_rules! boxed_vec {
-    ($( $op:expr ),*) => {
-        vec![
-            $(
-                Box::new($op),
-            )*
-        ]
-    };
-}
-
-macro_rules! reg {
-    ($id:expr) => {
-        Register::new($id)
-    };
-}
-
-macro_rules! int {
-    ($value:expr) => {
-        Integer::new($value)
-    };
-}
-
-// 先頭からN-2までのレジスタを割り当てて、残りはStore, Loadしてメモリに置く
-fn allocate_registers1(opcodes: Vec<OpeCode>, register_num: usize) -> Vec<OpeCode> {
-    let mut result: Vec<OpeCode> = Vec::new();
-
-    // register id -> address
-    let mut reg_addr_map: HashMap<usize, usize> = HashMap::new();
-
-    let alloc_dst_reg = |reg: Register, reg_addr_map: &mut HashMap<usize, usize>| {
-        let temp_reg = register_num - 1;
-
-        if reg.id <= register_num - 2 {
-            (reg, None)
-        } else {
-            let new_addr = reg_addr_map.len();
-            reg_addr_map.entry(reg.id).or_insert(new_addr);
-
-            (reg!(temp_reg), Some(Integer::new(*reg_addr_map.get(&reg.id).unwrap() as i32)))
-        }
-    };
-
-    let alloc_src_reg = |reg: Register, temp_reg: usize, reg_addr_map: &mut HashMap<usize, usize>, result: &mut Vec<OpeCode>| {
-        if reg.id <= register_num - 2 {
-            reg
-        } else {
-            let new_addr = reg_addr_map.len();
-            reg_addr_map.entry(reg.id).or_insert(new_addr);
-
-            let addr = Integer::new(*reg_addr_map.get(&reg.id).unwrap() as i32);
-            result.push(OpeCode::Load{ dst: reg!(temp_reg), src: addr });
-
-            reg!(temp_reg)
-        }
+fn parse_args(args: &[String], registry: &Registry) -> Result<Command, CliError> {
+    let subcommand = match args.first() {
+        Some(subcommand) => subcommand.as_str(),
+        None => return Err(CliError::Usage("no subcommand given".to_string())),
     };
 
-    for opcode in &opcodes {
-        let opcode = opcode.clone();
-        match opcode {
-            OpeCode::LdI { dst, value } => {
-                match alloc_dst_reg(dst, &mut reg_addr_map) {
-                    (reg, None) => result.push(OpeCode::LdI{ dst: reg, value: value }),
-                    (reg, Some(addr)) => {
-                        result.push(OpeCode::LdI{ dst: reg.clone(), value: value});
-                        result.push(OpeCode::Store{ dst: addr, src: reg});
-                    },
-                }
-            },
-            OpeCode::Add { dst, src1, src2 } => {
-                let src1 = alloc_src_reg(src1, register_num - 1, &mut reg_addr_map, &mut result);
-                let src2 = alloc_src_reg(src2, register_num,     &mut reg_addr_map, &mut result);
-
-                match alloc_dst_reg(dst, &mut reg_addr_map) {
-                    (reg, None) => result.push(OpeCode::Add{ dst: reg, src1: src1, src2: src2 }),
-                    (reg, Some(addr)) => {
-                        result.push(OpeCode::Add{ dst: reg.clone(), src1: src1, src2: src2 });
-                        result.push(OpeCode::Store{ dst: addr, src: reg });
-                    },
-                }
-            },
-            OpeCode::Store { dst, src } => {
-                let src = alloc_src_reg(src, register_num, &mut reg_addr_map, &mut result);
-                result.push(OpeCode::Store{ dst: dst, src: src });
+    // 値を取らないオプションは""を入れておく
+    let mut options: HashMap<&str, &str> = HashMap::new();
+    let mut input = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--algo" | "--regs" | "--mem" | "--overflow" | "--steps" | "-o" | "--seed" | "--iterations" | "--len" | "--live" => {
+                let value = rest.next().ok_or_else(|| CliError::Usage(format!("`{}` needs a value", arg)))?;
+                options.insert(arg.as_str(), value.as_str());
             },
-            OpeCode::Load { dst, src } => {
-                match alloc_dst_reg(dst, &mut reg_addr_map) {
-                    (reg, None) => result.push(OpeCode::Load{ dst: reg, src: src }),
-                    (reg, Some(addr)) => {
-                        result.push(OpeCode::Load{ dst: reg.clone(), src: src });
-                        result.push(OpeCode::Store{ dst: addr, src: reg });
-                    },
-                }
-            },
-            OpeCode::Print { src } => {
-                let src = alloc_src_reg(src, register_num, &mut reg_addr_map, &mut result);
-                result.push(OpeCode::Print{ src: src });
+            "--dump" | "--spill-costs" => {
+                options.insert(arg.as_str(), "");
             },
+            _ if arg.starts_with('-') => return Err(CliError::Usage(format!("unknown option `{}`", arg))),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(CliError::Usage(format!("unexpected argument `{}`", arg))),
         }
     }
 
-    result
-}
-
-#[derive(Clone, PartialEq)]
-enum LiveRangeCell {
-    Dead, Birth, Live, Used, EndPoint
-}
-
-impl LiveRangeCell {
-    fn is_live(&self) -> bool {
-        self == &LiveRangeCell::Birth || self == &LiveRangeCell::Live || self == &LiveRangeCell::Used
-    }
-
-    fn is_used(&self) -> bool {
-        self == &LiveRangeCell::Birth || self == &LiveRangeCell::Used || self == &LiveRangeCell::EndPoint
-    }
-}
-
-fn is_empty<T: PartialEq>(matrix_graph: &Vec<Vec<T>>, null_value: T) -> bool {
-    for row in matrix_graph {
-        for elem in row {
-            if elem != &null_value {
-                return false
-            }
-        }
+    let allowed: &[&str] = match subcommand {
+        "alloc" => &["--algo", "--regs", "--spill-costs", "-o"],
+        "reduce" => &["--algo", "--regs", "-o"],
+        "run" => &["--regs", "--mem", "--overflow", "--steps", "--dump"],
+        "bench" | "check" => &["--regs"],
+        "fuzz" => &["--seed", "--iterations", "--len", "--live", "--regs", "-o"],
+        _ => return Err(CliError::Usage(format!("unknown subcommand `{}`", subcommand))),
+    };
+    if let Some(option) = options.keys().find(|option| !allowed.contains(option)) {
+        return Err(CliError::Usage(format!("`{}` does not take `{}`", subcommand, option)));
     }
-    return true
-}
 
-// Chatinのアルゴリズム(干渉グラフを用いる)
-fn allocate_registers2(opcodes: Vec<OpeCode>, max_register_num: usize) -> Vec<OpeCode> {
-    // レジスタは1から順に使用されていると仮定
-    let register_num = opcodes.iter().map(|op| {
-        match op.clone() {
-            OpeCode::Add { dst, src1, src2 } => {
-                vec![dst, src1, src2].iter().max_by_key(|reg| reg.id).unwrap().id
-            },
-            OpeCode::LdI { dst, value: _ } => dst.id,
-            OpeCode::Store { dst: _, src } => src.id,
-            OpeCode::Load { dst, src: _ } => dst.id,
-            OpeCode::Print { src } => src.id,
+    if subcommand == "fuzz" {
+        if let Some(input) = input {
+            return Err(CliError::Usage(format!("unexpected argument `{}`", input)));
         }
-    }).max().unwrap() + 1;
 
-    // 生存区間の生成
-    let mut live_range: Vec<Vec<LiveRangeCell>> = Vec::new();
-    for _ in 0..register_num {
-        let mut row: Vec<LiveRangeCell> = Vec::new();
-        row.resize(opcodes.len(), LiveRangeCell::Dead);
-        live_range.push(row);
+        let number = |option: &str, default: usize| match options.get(option) {
+            Some(arg) => arg.parse().map_err(|_| CliError::Usage(format!("bad number `{}` for `{}`", arg, option))),
+            None => Ok(default),
+        };
+        let seed = number("--seed", 0)? as u64;
+        let iterations = number("--iterations", 1000)? as u64;
+        let mut config = GenConfig::default();
+        config.length = number("--len", config.length)?;
+        config.live_values = number("--live", config.live_values)?;
+        config.registers = config.registers.max(config.live_values * 2);
+        let registers = match options.get("--regs") {
+            Some(arg) => parse_register_range(arg)?,
+            None => MIN_REGISTER_NUM..10,
+        };
+        let end = seed.checked_add(iterations)
+            .ok_or_else(|| CliError::Usage(format!("seeds from {} for {} iteration(s) do not fit in u64", seed, iterations)))?;
+        return Ok(Command::Fuzz { seeds: seed..end, config, registers, output: options.get("-o").map(|s| s.to_string()) });
     }
 
-    for (i, opcode) in opcodes.iter().enumerate() {
-        match opcode.clone() {
-            OpeCode::Add { dst, src1, src2 } => {
-                live_range[dst.id][i]  = LiveRangeCell::Birth;
-                live_range[src1.id][i] = LiveRangeCell::Used;
-                live_range[src2.id][i] = LiveRangeCell::Used;
-            },
-            OpeCode::LdI { dst, value: _ } => {
-                live_range[dst.id][i]  = LiveRangeCell::Birth;
-            },
-            OpeCode::Store { dst: _, src } => {
-                live_range[src.id][i]  = LiveRangeCell::Used;
-            },
-            OpeCode::Load { dst, src: _ } => {
-                live_range[dst.id][i]  = LiveRangeCell::Birth;
-            },
-            OpeCode::Print { src } => {
-                live_range[src.id][i]  = LiveRangeCell::Used;
-            },
-        }
-    }
-
-    let mut living: Vec<bool> = Vec::new();
-    living.resize(register_num, false);
-
-    for reg_id in 0..live_range.len() {
-        let row = &mut live_range[reg_id];
-        for i in (0..row.len()).rev() {
-            let cell = row[i].clone();
-            match cell {
-                LiveRangeCell::Dead => {
-                    if living[reg_id] {
-                        row[i] = LiveRangeCell::Live;
-                    }
-                },
-                LiveRangeCell::Live | LiveRangeCell::Used => {
-                    if !living[reg_id] {
-                        row[i] = LiveRangeCell::EndPoint;
-                    }
-                    living[reg_id] = true;
-                },
-                LiveRangeCell::Birth => {
-                    living[reg_id] = false;
-                },
-                LiveRangeCell::EndPoint => {},
+    let input = input.ok_or_else(|| CliError::Usage("no input file given".to_string()))?;
+
+    match subcommand {
+        "alloc" | "reduce" => {
+            let algo = match options.get("--algo") {
+                Some(name) if registry.names().contains(name) => name.to_string(),
+                Some(name) => return Err(CliError::Usage(format!("unknown algorithm `{}`", name))),
+                None => return Err(CliError::Usage(format!("`{}` needs `--algo`", subcommand))),
+            };
+            let register_num = match options.get("--regs") {
+                Some(arg) => parse_register_num(arg)?,
+                None => return Err(CliError::Usage(format!("`{}` needs `--regs`", subcommand))),
+            };
+            let output = options.get("-o").map(|s| s.to_string());
+            if subcommand == "alloc" {
+                Ok(Command::Alloc { algo, register_num, spill_costs: options.contains_key("--spill-costs"), input, output })
+            } else {
+                Ok(Command::Reduce { algo, register_num, input, output })
             }
-        }
+        },
+        "run" => {
+            let register_num = match options.get("--regs") {
+                Some(arg) => Some(parse_register_num(arg)?),
+                None => None,
+            };
+            let memory_size = match options.get("--mem") {
+                Some(arg) => Some(arg.parse().map_err(|_| CliError::Usage(format!("bad memory size `{}`", arg)))?),
+                None => None,
+            };
+            let overflow = match options.get("--overflow") {
+                Some(&"wrapping") | None => Overflow::Wrapping,
+                Some(&"checked") => Overflow::Checked,
+                Some(&"saturating") => Overflow::Saturating,
+                Some(arg) => return Err(CliError::Usage(format!("unknown overflow behaviour `{}`", arg))),
+            };
+            let max_steps = match options.get("--steps") {
+                Some(arg) => Some(arg.parse().map_err(|_| CliError::Usage(format!("bad step limit `{}`", arg)))?),
+                None => None,
+            };
+            Ok(Command::Run { register_num, memory_size, overflow, max_steps, dump: options.contains_key("--dump"), input })
+        },
+        "bench" => {
+            let registers = match options.get("--regs") {
+                Some(arg) => parse_register_range(arg)?,
+                None => 4..10,
+            };
+            Ok(Command::Bench { registers, input })
+        },
+        _ => {
+            let registers = match options.get("--regs") {
+                Some(arg) => parse_register_range(arg)?,
+                None => MIN_REGISTER_NUM..16,
+            };
+            Ok(Command::Check { registers, input })
+        },
     }
+}
 
-    let mut reg_map: HashMap<usize, usize> = HashMap::new();
-    let mut spilled_reg: Vec<usize> = Vec::new();
-
-    loop {
-        let register_num = live_range.len();
-
-        // for (reg_id, row) in live_range.iter().enumerate() {
-        //     if reg_id == 0 {
-        //         continue;
-        //     }
-        //
-        //     print!("{:02}: ", reg_id);
-        //     for cell in row.iter() {
-        //         let ch = match *cell {
-        //             LiveRangeCell::Dead => '.',
-        //             LiveRangeCell::Birth => '*',
-        //             LiveRangeCell::Live => '-',
-        //             LiveRangeCell::Used => '=',
-        //             LiveRangeCell::EndPoint => 'x',
-        //         };
-        //         print!("{}", ch);
-        //     }
-        //     println!("");
-        // }
-
-        // 干渉グラフの生成
-        let mut interf_matrix: Vec<Vec<bool>> = Vec::new();
-        for _ in 0..register_num {
-            let mut row: Vec<bool> = Vec::new();
-            row.resize(register_num, false);
-            interf_matrix.push(row);
-        }
-
-        for (reg_id1, row1) in live_range.clone().iter().enumerate() {
-            for (reg_id2, row2) in live_range.iter().enumerate() {
-                if reg_id1 == reg_id2 {
-                    continue
-                }
+fn read_program(path: &str) -> Result<Vec<OpeCode>, CliError> {
+    let source = fs::read_to_string(path).map_err(|err| CliError::Failure(format!("{}: {}", path, err)))?;
+    parser::parse(&source).map_err(|err| CliError::Failure(format!("{}:{}", path, err)))
+}
 
-                for i in 0..row1.len() {
-                    if row1[i].is_live() && row2[i].is_live() {
-                        interf_matrix[reg_id1][reg_id2] = true;
-                        interf_matrix[reg_id2][reg_id1] = true;
-                    }
+fn execute(command: Command, registry: &Registry) -> Result<(), CliError> {
+    match command {
+        Command::Alloc { algo, register_num, spill_costs, input, output } => {
+            let opcodes = read_program(&input)?;
+            let allocator = registry.create(&algo, AllocConfig::new(register_num)).unwrap();
+            let allocation = allocator.allocate(&opcodes)
+                .map_err(|err| CliError::Failure(format!("{}: {}", input, err)))?;
+            if spill_costs {
+                for decision in &allocation.spill_decisions {
+                    eprintln!("spill {}", decision);
                 }
             }
-        }
-
-        // for (i, row) in interf_matrix.iter().enumerate() {
-        //     for (j, cell) in row.iter().enumerate() {
-        //         if i == 0 || j == 0 {
-        //             continue;
-        //         }
-        //
-        //         let ch = if *cell {
-        //                 'X'
-        //             } else if i == j {
-        //                 '\\'
-        //             } else {
-        //                 '.'
-        //             };
-        //         print!("{}", ch);
-        //     }
-        //     println!("");
-        // }
-
-        let mut spill_list: Vec<usize> = Vec::new();
-        let mut removed_regs: Vec<usize> = Vec::new();
-
-        let mut interf_matrix_cloned = interf_matrix.clone();
-
-        while !is_empty(&interf_matrix_cloned, false) || removed_regs.len() < register_num {
-            // iとつながっているノードの個数
-            let im = interf_matrix_cloned.clone();
-            let degs = im.iter().map(|row| row.iter().filter(|&&cell| cell).count()).collect::<Vec<_>>();
-
-            let threshold = max_register_num - 2;
-
-            let reg_id = degs.iter().enumerate().position(|(reg_id, &deg)| {
-                    deg < threshold && removed_regs.iter().find(|&&r| r == reg_id) == None
-                })
-                .unwrap_or_else(|| {
-                    let reg_id = degs.iter().position(|&deg| deg >= threshold).unwrap();
-                    spill_list.push(reg_id);
-                    reg_id
-                });
-            // 干渉グラフからreg_idを取り除く
-            for i in 0..register_num {
-                interf_matrix_cloned[i][reg_id] = false;
-                interf_matrix_cloned[reg_id][i] = false;
+            let text = printer::Listing::new(&allocation.code)
+                .comment(0, format!("{} --regs {}", algo, register_num))
+                .to_string();
+            match output {
+                Some(path) => fs::write(&path, text).map_err(|err| CliError::Failure(format!("{}: {}", path, err)))?,
+                None => print!("{}", text),
             }
-            removed_regs.push(reg_id);
-        }
-
-        if spill_list.len() == 0 {
-            // 塗る
-            for &reg_id in removed_regs.iter().rev() {
-                let mut is_painted: Vec<bool> = Vec::new();
-                is_painted.resize(max_register_num + 1, false);
-
-                for (reg_id2, &connected) in interf_matrix[reg_id].iter().enumerate() {
-                    if connected {
-                        if let Some(&color) = reg_map.get(&reg_id2) {
-                            is_painted[color] = true;
-                        }
-                    }
-                }
-
-                let mut color = 1;
-                for _ in 0..is_painted.len() {
-                    if !is_painted[color] {
-                        break;
+        },
+        Command::Run { register_num, memory_size, overflow, max_steps, dump, input } => {
+            let opcodes = read_program(&input)?;
+            let register_num = register_num.unwrap_or_else(|| max_register_id(&opcodes));
+            let mut config = VmConfig::new(register_num);
+            config.memory_size = memory_size.unwrap_or(config.memory_size);
+            config.overflow = overflow;
+            config.max_steps = max_steps.unwrap_or(config.max_steps);
+
+            let result = run_vm_with_sink(&opcodes, &config, &mut Stdout)
+                .map_err(|err| CliError::Failure(format!("{}: {}", input, err)))?;
+            if dump {
+                println!();
+                print!("{}", result.dump());
+            }
+        },
+        Command::Bench { registers, input } => {
+            let opcodes = read_program(&input)?;
+            println!("reg num, {}", registry.names().join(", "));
+            for i in registers {
+                let counts: Vec<String> = registry.create_all(&AllocConfig::new(i)).iter()
+                    .map(|allocator| match allocator.allocate(&opcodes) {
+                        Ok(allocation) => allocation.stats.instructions_after.to_string(),
+                        Err(_) => "-".to_string(),
+                    })
+                    .collect();
+                println!("{}, {}", i, counts.join(", "));
+            }
+        },
+        Command::Check { registers, input } => {
+            let opcodes = read_program(&input)?;
+            let mut failures = 0;
+            for register_num in registers {
+                for allocator in registry.create_all(&AllocConfig::new(register_num)) {
+                    if let Err(failure) = fuzz::check(&opcodes, &*allocator) {
+                        println!("{}", failure);
+                        failures += 1;
                     }
-                    color += 1;
                 }
-
-                reg_map.insert(reg_id, color);
             }
-
-            break;
-        } else {
-            // spill
-            spilled_reg = spill_list.clone();
-
-            for &reg_id in &spill_list {
-                for i in 0..live_range[reg_id].len() {
-                    live_range[reg_id][i] = LiveRangeCell::Dead;
-                }
+            if failures > 0 {
+                return Err(CliError::Failure(format!("{}: {} failure(s)", input, failures)));
             }
-        }
-    }
-
-    // for (r1, r2) in &reg_map {
-    //     println!("{} -> {}", r1, r2);
-    // }
-    //
-    // for &reg_id in &spilled_reg {
-    //     println!("{}", reg_id);
-    // }
-
-    let mut result: Vec<OpeCode> = Vec::new();
-
-    // register id -> address
-    let mut reg_addr_map: HashMap<usize, usize> = HashMap::new();
-
-    // for spilled registers
-    let alloc_dst_reg = |reg_id: usize, original_reg_id: usize, reg_addr_map: &mut HashMap<usize, usize>| {
-        let temp_reg = max_register_num - 1;
-
-        if spilled_reg.iter().find(|&&r| r == reg_id) == None {
-            (reg!(reg_id), None)
-        } else {
-            let new_addr = reg_addr_map.len();
-            reg_addr_map.entry(original_reg_id).or_insert(new_addr);
-
-            (reg!(temp_reg), Some(Integer::new(*reg_addr_map.get(&original_reg_id).unwrap() as i32)))
-        }
-    };
-
-    let alloc_src_reg = |reg_id: usize, original_reg_id: usize, temp_reg: usize, reg_addr_map: &mut HashMap<usize, usize>, result: &mut Vec<OpeCode>| {
-        if spilled_reg.iter().find(|&&r| r == reg_id) == None {
-            None
-        } else {
-            let new_addr = reg_addr_map.len();
-            reg_addr_map.entry(original_reg_id).or_insert(new_addr);
-
-            let addr = Integer::new(*reg_addr_map.get(&original_reg_id).unwrap() as i32);
-            result.push(OpeCode::Load{ dst: reg!(temp_reg), src: addr });
-
-            Some(reg!(temp_reg))
-        }
-    };
-
-    for opcode in &opcodes {
-        let opcode = opcode.clone();
-        match opcode {
-            OpeCode::LdI { dst, value } => {
-                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
-                    (reg, None) => {
-                        let &dst = reg_map.get(&reg.id).unwrap();
-                        result.push(OpeCode::LdI{ dst: reg!(dst), value: value });
-                    },
-                    (reg, Some(addr)) => {
-                        result.push(OpeCode::LdI{ dst: reg.clone(), value: value});
-                        result.push(OpeCode::Store{ dst: addr, src: reg});
-                    },
-                }
-            },
-            OpeCode::Add { dst, src1, src2 } => {
-
-                let src1 = alloc_src_reg(src1.id, src1.id, max_register_num - 1, &mut reg_addr_map, &mut result)
-                           .unwrap_or_else(|| reg!(*reg_map.get(&src1.id).unwrap()));
-                let src2 = alloc_src_reg(src2.id, src2.id, max_register_num,     &mut reg_addr_map, &mut result)
-                           .unwrap_or_else(|| reg!(*reg_map.get(&src2.id).unwrap()));
-
-                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
-                    (reg, None) => {
-                        let &dst = reg_map.get(&reg.id).unwrap();
-                        result.push(OpeCode::Add{ dst: reg!(dst), src1: src1, src2: src2 });
-                    },
-                    (reg, Some(addr)) => {
-                        result.push(OpeCode::Add{ dst: reg.clone(), src1: src1, src2: src2 });
-                        result.push(OpeCode::Store{ dst: addr, src: reg });
-                    },
+        },
+        Command::Fuzz { seeds, config, registers, output } => {
+            panic::set_hook(Box::new(|_| {}));
+            let failures = fuzz::fuzz(seeds.clone(), &config, registry, registers);
+            for failure in &failures {
+                println!("; seed {}: {}", failure.seed, failure.failure);
+                println!("{}", printer::Listing::new(&failure.opcodes));
+
+                if let Some(ref dir) = output {
+                    let (algo, register_num) = failure.failure.allocator();
+                    let allocator = registry.create(algo, AllocConfig::new(register_num)).unwrap();
+                    let reduced = fuzz::reduce(&failure.opcodes, &*allocator, &failure.failure);
+                    let path = Path::new(dir).join(format!("seed-{}.s", failure.seed));
+                    fs::write(&path, fuzz::repro(&reduced, &failure.failure))
+                        .map_err(|err| CliError::Failure(format!("{}: {}", path.display(), err)))?;
                 }
-            },
-            OpeCode::Store { dst, src } => {
-                let src = alloc_src_reg(src.id, src.id, max_register_num, &mut reg_addr_map, &mut result)
-                           .unwrap_or_else(|| reg!(*reg_map.get(&src.id).unwrap()));
-                result.push(OpeCode::Store{ dst: dst, src: src });
-            },
-            OpeCode::Load { dst, src } => {
-                match alloc_dst_reg(dst.id, dst.id, &mut reg_addr_map) {
-                    (reg, None) => {
-                        let &dst = reg_map.get(&reg.id).unwrap();
-                        result.push(OpeCode::Load{ dst: reg!(dst), src: src });
-                    },
-                    (reg, Some(addr)) => {
-                        result.push(OpeCode::Load{ dst: reg.clone(), src: src });
-                        result.push(OpeCode::Store{ dst: addr, src: reg});
-                    },
-                }
-            },
-            OpeCode::Print { src } => {
-                let src = alloc_src_reg(src.id, src.id, max_register_num, &mut reg_addr_map, &mut result)
-                           .unwrap_or_else(|| reg!(*reg_map.get(&src.id).unwrap()));
-                result.push(OpeCode::Print{ src: src });
-            },
-        }
-    }
-
-    result
-}
-
-fn run_vm(opcodes: Vec<OpeCode>, register_num: usize) {
-    let mut reg = Vec::new();
-    reg.resize(register_num + 1, 0);
-    let mut mem = [0; 1024];
-
-    for opcode in &opcodes {
-        let opcode = opcode.clone();
-        match opcode {
-            OpeCode::LdI { dst, value } => {
-                reg[dst.id] = value.value;
-            },
-            OpeCode::Add { dst, src1, src2 } => {
-                reg[dst.id] = reg[src1.id] + reg[src2.id];
-            },
-            OpeCode::Store { dst, src } => {
-                mem[dst.value as usize] = reg[src.id];
-            },
-            OpeCode::Load { dst, src } => {
-                reg[dst.id] = mem[src.value as usize];
-            },
-            OpeCode::Print { src } => {
-                println!("{}", reg[src.id]);
-            },
-        }
-    }
-
-    println!("");
-    println!("registers");
-    for i in 1..reg.len() {
-        println!("  %{} = {}", i, reg[i]);
-    }
-    println!("memory");
-    for addr in 0..mem.len() {
-        if mem[addr] != 0 {
-            println!("  {}: {}", addr, mem[addr]);
-        }
+            }
+            if !failures.is_empty() {
+                return Err(CliError::Failure(format!("{} of {} program(s) failed", failures.len(), seeds.end - seeds.start)));
+            }
+        },
+        Command::Reduce { algo, register_num, input, output } => {
+            panic::set_hook(Box::new(|_| {}));
+            let opcodes = read_program(&input)?;
+            let allocator = registry.create(&algo, AllocConfig::new(register_num)).unwrap();
+            let failure = fuzz::check(&opcodes, &*allocator).err()
+                .ok_or_else(|| CliError::Failure(format!("{}: {} --regs {} does not fail", input, algo, register_num)))?;
+
+            let reduced = fuzz::reduce(&opcodes, &*allocator, &failure);
+            let text = fuzz::repro(&reduced, &failure);
+            match output {
+                Some(path) => fs::write(&path, text).map_err(|err| CliError::Failure(format!("{}: {}", path, err)))?,
+                None => print!("{}", text),
+            }
+        },
     }
+    Ok(())
 }
 
 fn main() {
-    let opcodes: Vec<OpeCode> = vec![
-        // OpeCode::LdI{ dst: reg!(1), value: int!(1)},
-        // OpeCode::LdI{ dst: reg!(2), value: int!(2)},
-        // OpeCode::LdI{ dst: reg!(3), value: int!(3)},
-        // OpeCode::LdI{ dst: reg!(4), value: int!(4)},
-        //
-        // OpeCode::Add{ dst: reg!(5), src1: reg!(1), src2: reg!(2)},
-        // OpeCode::Add{ dst: reg!(6), src1: reg!(5), src2: reg!(3)},
-        // OpeCode::Add{ dst: reg!(7), src1: reg!(6), src2: reg!(4)},
-        //
-        // OpeCode::Print{ src: reg!(7) }, // => 10
-
-        OpeCode::LdI{ dst: reg!(1), value: int!(1)},
-        OpeCode::LdI{ dst: reg!(2), value: int!(2)},
-        OpeCode::LdI{ dst: reg!(3), value: int!(3)},
-        OpeCode::LdI{ dst: reg!(4), value: int!(4)},
-        OpeCode::LdI{ dst: reg!(5), value: int!(5)},
-        OpeCode::LdI{ dst: reg!(6), value: int!(6)},
-
-        OpeCode::Add{ dst: reg!(7), src1: reg!(1), src2: reg!(2)},
-        OpeCode::Add{ dst: reg!(8), src1: reg!(3), src2: reg!(4)},
-        OpeCode::Add{ dst: reg!(9), src1: reg!(5), src2: reg!(6)},
-
-        OpeCode::Print{ src: reg!(7) }, // => 3
-        OpeCode::Print{ src: reg!(8) }, // => 7
-        OpeCode::Print{ src: reg!(9) }, // => 11
-    ];
-
-    // for opcode in &opcodes {
-    //     println!("{}", opcode.to_string());
-    // }
-
-    // println!("");
-
-    println!("reg num, algo1, algo2");
-
-    for i in 4..10 {
-        let opcodes1 = allocate_registers1(opcodes.clone(), i);
-        let opcodes2 = allocate_registers2(opcodes.clone(), i);
-
-        // println!("algo1");
-        // for opcode in &opcodes1 {
-        //     println!("{}", opcode.to_string());
-        // }
-        // println!("");
-        //
-        // println!("algo2");
-        // for opcode in &opcodes2 {
-        //     println!("{}", opcode.to_string());
-        // }
-        // println!("");
-
-        println!("{}, {}, {}", i, opcodes1.len(), opcodes2.len());
-
-        // run_vm(opcodes1, i);
-        // run_vm(opcodes2, i);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let registry = Registry::default();
+    let result = parse_args(&args, &registry).and_then(|command| execute(command, &registry));
+
+    match result {
+        Ok(()) => {},
+        Err(CliError::Usage(message)) => {
+            eprintln!("error: {}", message);
+            eprintln!("{}", USAGE);
+            eprintln!("algorithms: {}", registry.names().join(", "));
+            process::exit(2);
+        },
+        Err(CliError::Failure(message)) => {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        },
     }
 }
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use ir::{Integer, Label, OpeCode, Register};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnknownMnemonic(String),
+    BadRegister(String),
+    BadImmediate(String),
+    ImmediateOverflow(String),
+    WrongOperandCount { mnemonic: String, expected: usize, found: usize },
+    BadLabel(String),
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+}
+
+/// パースエラー (line, columnは1始まり)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match self.kind {
+            ParseErrorKind::UnknownMnemonic(ref name) => write!(f, "unknown mnemonic `{}`", name),
+            ParseErrorKind::BadRegister(ref token) => write!(f, "bad register `{}`", token),
+            ParseErrorKind::BadImmediate(ref token) => write!(f, "bad immediate `{}`", token),
+            ParseErrorKind::ImmediateOverflow(ref token) => write!(f, "immediate `{}` does not fit in i32", token),
+            ParseErrorKind::WrongOperandCount { ref mnemonic, expected, found } => {
+                write!(f, "`{}` takes {} operand(s) but {} given", mnemonic, expected, found)
+            },
+            ParseErrorKind::BadLabel(ref token) => write!(f, "bad label `{}`", token),
+            ParseErrorKind::DuplicateLabel(ref name) => write!(f, "label `{}` is already defined", name),
+            ParseErrorKind::UndefinedLabel(ref name) => write!(f, "label `{}` is not defined", name),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+// 空白を除いたトークンとその桁を返す
+fn trim_token(text: &str, offset: usize) -> Token<'_> {
+    let start = text.len() - text.trim_start().len();
+    Token {
+        text: text.trim(),
+        column: offset + start + 1,
+    }
+}
+
+fn split_operands(text: &str, offset: usize) -> Vec<Token<'_>> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for (i, ch) in text.char_indices() {
+        if ch == ',' {
+            tokens.push(trim_token(&text[start..i], offset + start));
+            start = i + 1;
+        }
+    }
+    tokens.push(trim_token(&text[start..], offset + start));
+    tokens
+}
+
+struct LineParser<'a> {
+    line: usize,
+    mnemonic: Token<'a>,
+    operands: Vec<Token<'a>>,
+}
+
+impl<'a> LineParser<'a> {
+    fn error(&self, column: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { line: self.line, column, kind }
+    }
+
+    fn expect_operands(&self, expected: usize) -> Result<(), ParseError> {
+        if self.operands.len() == expected {
+            Ok(())
+        } else {
+            Err(self.error(self.mnemonic.column, ParseErrorKind::WrongOperandCount {
+                mnemonic: self.mnemonic.text.to_string(),
+                expected,
+                found: self.operands.len(),
+            }))
+        }
+    }
+
+    fn register(&self, n: usize) -> Result<Register, ParseError> {
+        let token = &self.operands[n];
+        let bad = || self.error(token.column, ParseErrorKind::BadRegister(token.text.to_string()));
+
+        if !token.text.starts_with('%') {
+            return Err(bad());
+        }
+        let digits = &token.text[1..];
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+            return Err(bad());
+        }
+        match digits.parse::<usize>() {
+            // レジスタは1始まり
+            Ok(id) if id >= 1 => Ok(Register::new(id)),
+            _ => Err(bad()),
+        }
+    }
+
+    fn integer(&self, n: usize) -> Result<Integer, ParseError> {
+        let token = &self.operands[n];
+        let digits = token.text.trim_start_matches('-');
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) || token.text.len() - digits.len() > 1 {
+            return Err(self.error(token.column, ParseErrorKind::BadImmediate(token.text.to_string())));
+        }
+        token.text.parse::<i32>()
+            .map(Integer::new)
+            .map_err(|_| self.error(token.column, ParseErrorKind::ImmediateOverflow(token.text.to_string())))
+    }
+
+    fn label(&self, token: &Token) -> Result<Label, ParseError> {
+        let mut chars = token.text.chars();
+        let head = chars.next().is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_' || ch == '.');
+        if head && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.') {
+            Ok(Label::new(token.text))
+        } else {
+            Err(self.error(token.column, ParseErrorKind::BadLabel(token.text.to_string())))
+        }
+    }
+
+    fn branch(&self, make: fn(Register, Register, Label) -> OpeCode) -> Result<OpeCode, ParseError> {
+        self.expect_operands(3)?;
+        Ok(make(self.register(0)?, self.register(1)?, self.label(&self.operands[2])?))
+    }
+
+    fn parse(&self) -> Result<OpeCode, ParseError> {
+        if self.mnemonic.text.ends_with(':') {
+            self.expect_operands(0)?;
+            let name = &self.mnemonic.text[..self.mnemonic.text.len() - 1];
+            let label = self.label(&Token { text: name, column: self.mnemonic.column })?;
+            return Ok(OpeCode::Label { label });
+        }
+
+        match self.mnemonic.text {
+            "loadi" => {
+                self.expect_operands(2)?;
+                Ok(OpeCode::LdI { dst: self.register(0)?, value: self.integer(1)? })
+            },
+            "add" => {
+                self.expect_operands(3)?;
+                Ok(OpeCode::Add { dst: self.register(0)?, src1: self.register(1)?, src2: self.register(2)? })
+            },
+            "store" => {
+                self.expect_operands(2)?;
+                Ok(OpeCode::Store { dst: self.integer(0)?, src: self.register(1)? })
+            },
+            "load" => {
+                self.expect_operands(2)?;
+                Ok(OpeCode::Load { dst: self.register(0)?, src: self.integer(1)? })
+            },
+            "print" => {
+                self.expect_operands(1)?;
+                Ok(OpeCode::Print { src: self.register(0)? })
+            },
+            "mov" => {
+                self.expect_operands(2)?;
+                Ok(OpeCode::Mov { dst: self.register(0)?, src: self.register(1)? })
+            },
+            "jmp" => {
+                self.expect_operands(1)?;
+                Ok(OpeCode::Jmp { target: self.label(&self.operands[0])? })
+            },
+            "beq" => self.branch(|src1, src2, target| OpeCode::Beq { src1, src2, target }),
+            "bne" => self.branch(|src1, src2, target| OpeCode::Bne { src1, src2, target }),
+            "blt" => self.branch(|src1, src2, target| OpeCode::Blt { src1, src2, target }),
+            name => Err(self.error(self.mnemonic.column, ParseErrorKind::UnknownMnemonic(name.to_string()))),
+        }
+    }
+}
+
+/// source.sの形式のアセンブリをパースする
+/// `;` 以降はコメント、`name:`だけの行はラベル
+pub fn parse(source: &str) -> Result<Vec<OpeCode>, ParseError> {
+    let mut opcodes = Vec::new();
+    // ラベルを定義した位置と、分岐先として最初に使われた位置
+    let mut defined: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut used: Vec<(String, usize, usize)> = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let code = match line.find(';') {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+        if code.trim().is_empty() {
+            continue;
+        }
+
+        let start = code.len() - code.trim_start().len();
+        let rest = &code[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+
+        let parser = LineParser {
+            line: i + 1,
+            mnemonic: Token { text: &rest[..end], column: start + 1 },
+            operands: split_operands(&rest[end..], start + end),
+        };
+        let opcode = parser.parse()?;
+        match opcode {
+            OpeCode::Label { ref label } => {
+                if defined.insert(label.name.clone(), (parser.line, parser.mnemonic.column)).is_some() {
+                    return Err(parser.error(parser.mnemonic.column, ParseErrorKind::DuplicateLabel(label.name.clone())));
+                }
+            },
+            _ => {
+                if let Some(target) = opcode.target() {
+                    let column = parser.operands.last().map_or(parser.mnemonic.column, |token| token.column);
+                    used.push((target.name.clone(), parser.line, column));
+                }
+            },
+        }
+        opcodes.push(opcode);
+    }
+
+    if let Some((name, line, column)) = used.into_iter().find(|used| !defined.contains_key(&used.0)) {
+        return Err(ParseError { line, column, kind: ParseErrorKind::UndefinedLabel(name) });
+    }
+
+    Ok(opcodes)
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ir::OpeCode;
+
+/// 行末コメントを付けてプログラムを出力する
+/// 出力はparser::parseでそのまま読み直せる
+pub struct Listing<'a> {
+    opcodes: &'a [OpeCode],
+    comments: HashMap<usize, String>,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(opcodes: &'a [OpeCode]) -> Listing<'a> {
+        Listing {
+            opcodes,
+            comments: HashMap::new(),
+        }
+    }
+
+    /// index番目の命令にコメントを付ける
+    pub fn comment<S: Into<String>>(mut self, index: usize, comment: S) -> Listing<'a> {
+        self.comments.insert(index, comment.into());
+        self
+    }
+}
+
+impl<'a> fmt::Display for Listing<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, opcode) in self.opcodes.iter().enumerate() {
+            match self.comments.get(&i) {
+                Some(comment) => writeln!(f, "{:<20} ; {}", opcode.to_string(), comment)?,
+                None => writeln!(f, "{}", opcode)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Listing;
+    use parser::parse;
+    use ir::{Integer, Label, OpeCode, Register};
+
+    fn round_trip(opcodes: &[OpeCode]) {
+        let text = Listing::new(opcodes).comment(0, "first").to_string();
+        assert_eq!(parse(&text).unwrap(), opcodes);
+    }
+
+    #[test]
+    fn round_trip_sources() {
+        round_trip(&parse(include_str!("../source.s")).unwrap());
+        round_trip(&parse(include_str!("../dest.s")).unwrap());
+        round_trip(&parse(include_str!("../loop.s")).unwrap());
+    }
+
+    #[test]
+    fn round_trip_all_opecodes() {
+        round_trip(&[
+            OpeCode::LdI { dst: Register::new(1), value: Integer::new(-2147483648) },
+            OpeCode::Store { dst: Integer::new(3), src: Register::new(1) },
+            OpeCode::Load { dst: Register::new(12), src: Integer::new(3) },
+            OpeCode::Add { dst: Register::new(2), src1: Register::new(12), src2: Register::new(1) },
+            OpeCode::Print { src: Register::new(2) },
+            OpeCode::Mov { dst: Register::new(3), src: Register::new(2) },
+            OpeCode::Label { label: Label::new("loop") },
+            OpeCode::Beq { src1: Register::new(1), src2: Register::new(2), target: Label::new("loop") },
+            OpeCode::Bne { src1: Register::new(1), src2: Register::new(2), target: Label::new(".end") },
+            OpeCode::Blt { src1: Register::new(2), src2: Register::new(1), target: Label::new("loop") },
+            OpeCode::Jmp { target: Label::new("loop") },
+            OpeCode::Label { label: Label::new(".end") },
+        ]);
+    }
+
+    #[test]
+    fn undefined_and_duplicate_labels() {
+        assert_eq!(parse("jmp end\n").unwrap_err().to_string(), "1:5: label `end` is not defined");
+        assert_eq!(parse("a:\n a:\n").unwrap_err().to_string(), "2:2: label `a` is already defined");
+        assert_eq!(parse("1a:\n").unwrap_err().to_string(), "1:1: bad label `1a`");
+    }
+}
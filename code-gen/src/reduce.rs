@@ -0,0 +1,71 @@
+//! 失敗するプログラムを小さくする (delta debugging)
+
+use std::collections::{BTreeSet, HashMap};
+
+use ir::{OpeCode, Register};
+
+/// failsがtrueを返す間、命令を取り除き、レジスタ番号を詰める
+///
+/// failsは元のプログラムに対してtrueを返すこと
+pub fn minimize<F: FnMut(&[OpeCode]) -> bool>(opcodes: &[OpeCode], mut fails: F) -> Vec<OpeCode> {
+    let mut current = opcodes.to_vec();
+    loop {
+        let before = current.len();
+        current = remove_chunks(current, &mut fails);
+
+        // 最初に現れた順に付け直すと失敗しなくなるなら、番号の大小を保ったまま詰める
+        for renumbered in [renumber_registers(&current), compact_registers(&current)] {
+            if renumbered != current && fails(&renumbered) {
+                current = renumbered;
+                break;
+            }
+        }
+
+        if current.len() == before {
+            return current;
+        }
+    }
+}
+
+// 大きい塊から順に取り除いてみる
+fn remove_chunks<F: FnMut(&[OpeCode]) -> bool>(mut current: Vec<OpeCode>, fails: &mut F) -> Vec<OpeCode> {
+    let mut chunk = (current.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start < current.len() {
+            let end = (start + chunk).min(current.len());
+            let candidate: Vec<OpeCode> = current[..start].iter().chain(current[end..].iter()).cloned().collect();
+            if !candidate.is_empty() && fails(&candidate) {
+                current = candidate;
+            } else {
+                start = end;
+            }
+        }
+
+        if chunk == 1 {
+            return current;
+        }
+        chunk /= 2;
+    }
+}
+
+/// 最初に現れた順に%1, %2, ...と付け直す
+pub fn renumber_registers(opcodes: &[OpeCode]) -> Vec<OpeCode> {
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    opcodes.iter().map(|opcode| {
+        for reg in opcode.registers() {
+            let next = ids.len() + 1;
+            ids.entry(reg.id).or_insert(next);
+        }
+        opcode.map_registers(|reg| Register::new(ids[&reg.id]))
+    }).collect()
+}
+
+// 使っている番号を小さい順に%1, %2, ...と付け直す
+//
+// 割り当て方法は同じ重みのレジスタを番号順に選ぶので、大小を保てば同じように割り当てる
+fn compact_registers(opcodes: &[OpeCode]) -> Vec<OpeCode> {
+    let used: BTreeSet<usize> = opcodes.iter().flat_map(|opcode| opcode.registers()).map(|reg| reg.id).collect();
+    let ids: HashMap<usize, usize> = used.into_iter().zip(1..).collect();
+    opcodes.iter().map(|opcode| opcode.map_registers(|reg| Register::new(ids[&reg.id]))).collect()
+}
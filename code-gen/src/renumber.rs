@@ -0,0 +1,60 @@
+//! 仮想レジスタの番号を0から詰めた番号に付け直す
+//!
+//! `%1`と`%100000`だけを使うプログラムでも、表の大きさが使っているレジスタの数で済むようにする。
+//! 付け直した番号は0から始まるので、そのプログラムは`.s`としては出力できない
+
+use ir::{OpeCode, Register};
+
+/// 元の番号 <-> 詰めた番号
+///
+/// 元の番号の小さい順に0, 1, 2, ...を割り振るので、番号の大小関係は変わらない
+#[derive(Debug, Clone, PartialEq)]
+pub struct Renumbering {
+    // 詰めた番号 -> 元の番号 (昇順)
+    sparse: Vec<usize>,
+}
+
+impl Renumbering {
+    pub fn new(opcodes: &[OpeCode]) -> Renumbering {
+        let mut sparse: Vec<usize> = opcodes.iter().flat_map(|op| op.registers()).map(|reg| reg.id).collect();
+        sparse.sort_unstable();
+        sparse.dedup();
+        Renumbering { sparse }
+    }
+
+    /// 使われているレジスタの数
+    pub fn len(&self) -> usize {
+        self.sparse.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sparse.is_empty()
+    }
+
+    /// 元の番号 -> 詰めた番号
+    pub fn dense(&self, id: usize) -> Option<usize> {
+        self.sparse.binary_search(&id).ok()
+    }
+
+    /// 詰めた番号 -> 元の番号
+    ///
+    /// `len()`以上の番号 (付け直したあとに増えたレジスタ) は、元の最大の番号の次から順に割り振る
+    pub fn sparse(&self, index: usize) -> usize {
+        match self.sparse.get(index) {
+            Some(&id) => id,
+            None => self.sparse.last().map_or(1, |&id| id + 1) + (index - self.sparse.len()),
+        }
+    }
+
+    /// 元の番号のプログラム -> 詰めた番号のプログラム
+    pub fn apply(&self, opcodes: &[OpeCode]) -> Vec<OpeCode> {
+        opcodes.iter().map(|op| op.map_registers(|reg| {
+            Register::new(self.dense(reg.id).unwrap_or_else(|| panic!("{} is not in the renumbering", reg)))
+        })).collect()
+    }
+
+    /// 詰めた番号のプログラム -> 元の番号のプログラム
+    pub fn restore(&self, opcodes: &[OpeCode]) -> Vec<OpeCode> {
+        opcodes.iter().map(|op| op.map_registers(|reg| Register::new(self.sparse(reg.id)))).collect()
+    }
+}
@@ -0,0 +1,168 @@
+//! 命令を記号的に実行し、割り当て前後のプログラムが同じ値をPrintするか調べる
+//!
+//! `LdI`の定数は何番目の`LdI`かとその値の組、最初のメモリの中身はアドレスごとの記号として扱う。
+//! 同じ記号は同じ値なので、同じと判定できれば割り当ては正しい。
+//! 値が同じでも別の`LdI`の定数は区別するので、レジスタの取り違えも見つかる
+//! (割り当てで`LdI`の順番を入れ替えたり増やしたりすると、正しくても違うと判定される)
+
+use std::collections::HashMap;
+use std::fmt;
+
+use ir::OpeCode;
+
+/// `Terms`の中の式の番号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TermId(usize);
+
+/// 式
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    /// n番目に実行された`LdI`の定数 (n, 値)
+    Const(usize, i32),
+    /// 最初にメモリのアドレスaddrにあった値
+    Memory(i32),
+    /// 一度も書き込まれていないレジスタの値
+    Undefined(usize),
+    /// 2つの式の和 (小さい番号の式が先)
+    Add(TermId, TermId),
+}
+
+/// 式の表
+///
+/// 同じ式には同じ`TermId`を割り振るので、式の比較は番号の比較で済む
+#[derive(Debug, Default)]
+pub struct Terms {
+    nodes: Vec<Term>,
+    ids: HashMap<Term, TermId>,
+}
+
+impl Terms {
+    pub fn new() -> Terms {
+        Terms::default()
+    }
+
+    /// termの番号を返す。初めての式なら追加する
+    pub fn intern(&mut self, term: Term) -> TermId {
+        // 足し算は交換しても同じ
+        let term = match term {
+            Term::Add(a, b) if b < a => Term::Add(b, a),
+            term => term,
+        };
+        if let Some(&id) = self.ids.get(&term) {
+            return id;
+        }
+        let id = TermId(self.nodes.len());
+        self.nodes.push(term.clone());
+        self.ids.insert(term, id);
+        id
+    }
+
+    pub fn get(&self, id: TermId) -> &Term {
+        &self.nodes[id.0]
+    }
+
+    /// 読める形の式
+    pub fn show(&self, id: TermId) -> String {
+        match *self.get(id) {
+            Term::Const(n, value) => format!("c{}({})", n, value),
+            Term::Memory(addr) => format!("mem[{}]", addr),
+            Term::Undefined(reg) => format!("undef(%{})", reg),
+            Term::Add(a, b) => format!("({} + {})", self.show(a), self.show(b)),
+        }
+    }
+}
+
+fn read(terms: &mut Terms, reg: &HashMap<usize, TermId>, id: usize) -> TermId {
+    match reg.get(&id) {
+        Some(&term) => term,
+        None => terms.intern(Term::Undefined(id)),
+    }
+}
+
+/// プログラムを記号的に実行し、Printした式を順に返す
+///
+/// 分岐命令は扱えない (ラベルは何もしない命令として読み飛ばす)
+pub fn evaluate(opcodes: &[OpeCode], terms: &mut Terms) -> Vec<TermId> {
+    let mut reg: HashMap<usize, TermId> = HashMap::new();
+    let mut mem: HashMap<i32, TermId> = HashMap::new();
+    let mut consts = 0;
+    let mut output = Vec::new();
+
+    for opcode in opcodes {
+        match *opcode {
+            OpeCode::LdI { ref dst, ref value } => {
+                let term = terms.intern(Term::Const(consts, value.value));
+                consts += 1;
+                reg.insert(dst.id, term);
+            },
+            OpeCode::Add { ref dst, ref src1, ref src2 } => {
+                let a = read(terms, &reg, src1.id);
+                let b = read(terms, &reg, src2.id);
+                let term = terms.intern(Term::Add(a, b));
+                reg.insert(dst.id, term);
+            },
+            OpeCode::Store { ref dst, ref src } => {
+                let term = read(terms, &reg, src.id);
+                mem.insert(dst.value, term);
+            },
+            OpeCode::Load { ref dst, ref src } => {
+                let term = match mem.get(&src.value) {
+                    Some(&term) => term,
+                    None => terms.intern(Term::Memory(src.value)),
+                };
+                reg.insert(dst.id, term);
+            },
+            OpeCode::Print { ref src } => {
+                output.push(read(terms, &reg, src.id));
+            },
+            OpeCode::Mov { ref dst, ref src } => {
+                let term = read(terms, &reg, src.id);
+                reg.insert(dst.id, term);
+            },
+            OpeCode::Label { .. } => {},
+            OpeCode::Jmp { .. } | OpeCode::Beq { .. } | OpeCode::Bne { .. } | OpeCode::Blt { .. } => {
+                panic!("symbolic evaluation does not support branches: {}", opcode)
+            },
+        }
+    }
+
+    output
+}
+
+/// index番目のPrintの式が違う (Noneは出力がないこと)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub index: usize,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |term: &Option<String>| term.clone().unwrap_or_else(|| "nothing".to_string());
+        write!(f, "print #{} expected {} but got {}", self.index, show(&self.expected), show(&self.actual))
+    }
+}
+
+/// originalとallocatedが、どんな定数と最初のメモリに対しても同じ値をPrintするか調べる
+///
+/// どちらも分岐を含まないこと
+pub fn equivalent(original: &[OpeCode], allocated: &[OpeCode]) -> Result<(), Difference> {
+    let mut terms = Terms::new();
+    let expected = evaluate(original, &mut terms);
+    let actual = evaluate(allocated, &mut terms);
+
+    let len = expected.len().max(actual.len());
+    for index in 0..len {
+        let a = expected.get(index).cloned();
+        let b = actual.get(index).cloned();
+        if a != b {
+            return Err(Difference {
+                index,
+                expected: a.map(|id| terms.show(id)),
+                actual: b.map(|id| terms.show(id)),
+            });
+        }
+    }
+    Ok(())
+}
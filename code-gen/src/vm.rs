@@ -0,0 +1,267 @@
+use std::error;
+use std::fmt;
+
+use ir::{label_positions, Integer, Label, OpeCode, Register};
+
+/// Printの出力先
+pub trait OutputSink {
+    fn print(&mut self, value: i32);
+}
+
+/// 標準出力に1行ずつ書く
+pub struct Stdout;
+
+impl OutputSink for Stdout {
+    fn print(&mut self, value: i32) {
+        println!("{}", value);
+    }
+}
+
+impl OutputSink for Vec<i32> {
+    fn print(&mut self, value: i32) {
+        self.push(value);
+    }
+}
+
+/// 実行結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionResult {
+    /// Printした値
+    pub output: Vec<i32>,
+    /// 最後のレジスタの値 (`registers[i]`が`%i`, 0番は使わない)
+    pub registers: Vec<i32>,
+    /// 最後のメモリの内容
+    pub memory: Vec<i32>,
+    /// 実行した命令の数 (ループした分も数える)
+    pub executed: usize,
+    /// 命令ごとの実行回数
+    pub counts: Vec<usize>,
+}
+
+impl ExecutionResult {
+    /// レジスタと0でないメモリの内容を出力する
+    pub fn dump(&self) -> Dump<'_> {
+        Dump { result: self }
+    }
+}
+
+pub struct Dump<'a> {
+    result: &'a ExecutionResult,
+}
+
+impl<'a> fmt::Display for Dump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "registers")?;
+        for (i, value) in self.result.registers.iter().enumerate().skip(1) {
+            writeln!(f, "  %{} = {}", i, value)?;
+        }
+        writeln!(f, "memory")?;
+        for (addr, &value) in self.result.memory.iter().enumerate() {
+            if value != 0 {
+                writeln!(f, "  {}: {}", addr, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Addが溢れたときの動作
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrapping,
+    /// `VmErrorKind::Overflow`で止まる
+    Checked,
+    Saturating,
+}
+
+/// `VmConfig::new`のメモリのワード数
+pub const DEFAULT_MEMORY_SIZE: usize = 1024;
+
+/// VMの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmConfig {
+    /// %1から%register_numまで使える
+    pub register_num: usize,
+    /// 使えるアドレスは0からmemory_size - 1まで
+    pub memory_size: usize,
+    pub overflow: Overflow,
+    /// これだけ命令を実行しても終わらなければ`VmErrorKind::StepLimit`で止まる
+    pub max_steps: usize,
+}
+
+impl VmConfig {
+    /// メモリは1024ワード、Addは溢れたら折り返す、100万命令まで実行する
+    pub fn new(register_num: usize) -> VmConfig {
+        VmConfig {
+            register_num,
+            memory_size: DEFAULT_MEMORY_SIZE,
+            overflow: Overflow::Wrapping,
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmErrorKind {
+    RegisterOutOfRange { reg: usize },
+    AddressOutOfRange { addr: i32 },
+    Overflow,
+    UndefinedLabel { label: String },
+    StepLimit { max_steps: usize },
+}
+
+/// 実行時エラー (indexは失敗した命令の位置)
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmError {
+    pub index: usize,
+    pub kind: VmErrorKind,
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "instruction {}: ", self.index)?;
+        match self.kind {
+            VmErrorKind::RegisterOutOfRange { reg } => write!(f, "register %{} is out of range", reg),
+            VmErrorKind::AddressOutOfRange { addr } => write!(f, "address {} is out of range", addr),
+            VmErrorKind::Overflow => write!(f, "integer overflow"),
+            VmErrorKind::UndefinedLabel { ref label } => write!(f, "label `{}` is not defined", label),
+            VmErrorKind::StepLimit { max_steps } => write!(f, "did not halt within {} steps", max_steps),
+        }
+    }
+}
+
+impl error::Error for VmError {}
+
+struct Machine {
+    config: VmConfig,
+    index: usize,
+    reg: Vec<i32>,
+    mem: Vec<i32>,
+}
+
+impl Machine {
+    fn error(&self, kind: VmErrorKind) -> VmError {
+        VmError { index: self.index, kind }
+    }
+
+    fn reg_index(&self, reg: &Register) -> Result<usize, VmError> {
+        if reg.id >= 1 && reg.id <= self.config.register_num {
+            Ok(reg.id)
+        } else {
+            Err(self.error(VmErrorKind::RegisterOutOfRange { reg: reg.id }))
+        }
+    }
+
+    fn mem_index(&self, addr: &Integer) -> Result<usize, VmError> {
+        if addr.value >= 0 && (addr.value as usize) < self.mem.len() {
+            Ok(addr.value as usize)
+        } else {
+            Err(self.error(VmErrorKind::AddressOutOfRange { addr: addr.value }))
+        }
+    }
+
+    fn read(&self, reg: &Register) -> Result<i32, VmError> {
+        Ok(self.reg[self.reg_index(reg)?])
+    }
+
+    fn write(&mut self, reg: &Register, value: i32) -> Result<(), VmError> {
+        let i = self.reg_index(reg)?;
+        self.reg[i] = value;
+        Ok(())
+    }
+
+    fn add(&self, a: i32, b: i32) -> Result<i32, VmError> {
+        match self.config.overflow {
+            Overflow::Wrapping => Ok(a.wrapping_add(b)),
+            Overflow::Checked => a.checked_add(b).ok_or_else(|| self.error(VmErrorKind::Overflow)),
+            Overflow::Saturating => Ok(a.saturating_add(b)),
+        }
+    }
+}
+
+/// プログラムを実行する
+pub fn run_vm(opcodes: &[OpeCode], config: &VmConfig) -> Result<ExecutionResult, VmError> {
+    run_vm_with_sink(opcodes, config, &mut Vec::new())
+}
+
+/// プログラムを実行し、Printした値をsinkにも書く
+pub fn run_vm_with_sink(opcodes: &[OpeCode], config: &VmConfig, sink: &mut dyn OutputSink) -> Result<ExecutionResult, VmError> {
+    let mut machine = Machine {
+        config: config.clone(),
+        index: 0,
+        reg: vec![0; config.register_num + 1],
+        mem: vec![0; config.memory_size],
+    };
+    let labels = label_positions(opcodes);
+    let jump = |machine: &Machine, target: &Label| {
+        labels.get(target.name.as_str()).cloned()
+            .ok_or_else(|| machine.error(VmErrorKind::UndefinedLabel { label: target.name.clone() }))
+    };
+    let mut output = Vec::new();
+    let mut executed = 0;
+    let mut counts = vec![0; opcodes.len()];
+
+    while machine.index < opcodes.len() {
+        if executed == config.max_steps {
+            return Err(machine.error(VmErrorKind::StepLimit { max_steps: config.max_steps }));
+        }
+        executed += 1;
+        counts[machine.index] += 1;
+
+        let mut next = machine.index + 1;
+        match opcodes[machine.index] {
+            OpeCode::LdI { ref dst, ref value } => {
+                machine.write(dst, value.value)?;
+            },
+            OpeCode::Add { ref dst, ref src1, ref src2 } => {
+                let value = machine.add(machine.read(src1)?, machine.read(src2)?)?;
+                machine.write(dst, value)?;
+            },
+            OpeCode::Store { ref dst, ref src } => {
+                let addr = machine.mem_index(dst)?;
+                machine.mem[addr] = machine.read(src)?;
+            },
+            OpeCode::Load { ref dst, ref src } => {
+                let value = machine.mem[machine.mem_index(src)?];
+                machine.write(dst, value)?;
+            },
+            OpeCode::Print { ref src } => {
+                let value = machine.read(src)?;
+                output.push(value);
+                sink.print(value);
+            },
+            OpeCode::Mov { ref dst, ref src } => {
+                let value = machine.read(src)?;
+                machine.write(dst, value)?;
+            },
+            OpeCode::Label { .. } => {},
+            OpeCode::Jmp { ref target } => {
+                next = jump(&machine, target)?;
+            },
+            OpeCode::Beq { ref src1, ref src2, ref target } => {
+                if machine.read(src1)? == machine.read(src2)? {
+                    next = jump(&machine, target)?;
+                }
+            },
+            OpeCode::Bne { ref src1, ref src2, ref target } => {
+                if machine.read(src1)? != machine.read(src2)? {
+                    next = jump(&machine, target)?;
+                }
+            },
+            OpeCode::Blt { ref src1, ref src2, ref target } => {
+                if machine.read(src1)? < machine.read(src2)? {
+                    next = jump(&machine, target)?;
+                }
+            },
+        }
+        machine.index = next;
+    }
+
+    Ok(ExecutionResult {
+        output,
+        registers: machine.reg,
+        memory: machine.mem,
+        executed,
+        counts,
+    })
+}
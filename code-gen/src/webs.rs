@@ -0,0 +1,159 @@
+//! 同じ番号のレジスタへの複数の書き込みを、別々の生存区間 (web) に分ける
+//!
+//! 同じ使用に届く定義をunion-findでひとまとめにしたものを1つのwebとする。
+//! ブロックの入口で生きているレジスタごとに節点を置き、先行ブロックの出口に届く定義とまとめる。
+//! 各webに別の番号を付け直せば、割り当てではwebごとに色を塗れる
+
+use std::collections::{BTreeMap, HashMap};
+
+use bitset::BitSet;
+use cfg::Cfg;
+use ir::{max_register_id, OpeCode, Register};
+use liveness::block_liveness;
+
+/// webに分けた結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webs {
+    /// webごとにレジスタ番号を付け直したプログラム
+    pub code: Vec<OpeCode>,
+    /// webの番号 -> 元のレジスタ番号
+    pub original: BTreeMap<usize, usize>,
+}
+
+// union-find
+struct Classes {
+    parent: Vec<usize>,
+}
+
+impl Classes {
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b.max(a)] = a.min(b);
+        }
+    }
+}
+
+/// プログラムをwebに分け、番号を付け直す
+///
+/// 各レジスタの最初の定義を含むwebは元の番号のまま残し、それ以外のwebには
+/// 元のプログラムで使われていない番号を定義の順に割り振る。
+/// 書き込みが1回だけのプログラムはそのまま返る
+pub fn split_webs(opcodes: &[OpeCode]) -> Webs {
+    let len = opcodes.len();
+    let register_num = max_register_id(opcodes) + 1;
+    // 定義の番号: 命令の位置、またはlen + レジスタ番号 (入口で書き込まれていない値)
+    let entry_def = |reg_id: usize| len + reg_id;
+
+    let cfg = Cfg::new(opcodes);
+    let mut block_start = Vec::new();
+    let mut start = 0;
+    for block in &cfg.blocks {
+        block_start.push(start);
+        start += block.code.len();
+    }
+
+    // ブロックの中の各レジスタの最後の定義
+    let last_defs: Vec<HashMap<usize, usize>> = cfg.blocks.iter().enumerate().map(|(id, block)| {
+        block.code.iter().enumerate()
+            .filter_map(|(offset, opcode)| opcode.dst().map(|dst| (dst.id, block_start[id] + offset)))
+            .collect()
+    }).collect();
+
+    // 入口で生きているレジスタごとに、そこに届く定義をまとめる節点を作る
+    let order = cfg.reverse_postorder();
+    let mut reachable = BitSet::new(cfg.blocks.len());
+    for &id in &order {
+        reachable.insert(id);
+    }
+    let (live_in, _) = block_liveness(&cfg, register_num);
+    let mut classes = Classes { parent: (0..len + register_num).collect() };
+    let mut joins: Vec<HashMap<usize, usize>> = vec![HashMap::new(); cfg.blocks.len()];
+    for &id in &order {
+        for reg_id in live_in[id].iter() {
+            joins[id].insert(reg_id, classes.parent.len());
+            classes.parent.push(classes.parent.len());
+        }
+    }
+
+    // 出口に届く定義 (ブロックの中の最後の定義か、入口の節点) を後続の入口の節点とまとめる。
+    // 生きていないレジスタの定義はどの使用にも届かないのでまとめない
+    for &id in &order {
+        for (&reg_id, &join) in &joins[id] {
+            if id == cfg.entry {
+                classes.union(join, entry_def(reg_id));
+            }
+            for &pred in cfg.blocks[id].preds.iter().filter(|&&pred| reachable.contains(pred)) {
+                if let Some(&def) = last_defs[pred].get(&reg_id).or_else(|| joins[pred].get(&reg_id)) {
+                    classes.union(join, def);
+                }
+            }
+        }
+    }
+
+    // 使用 (命令の位置, 何番目のsrcか) -> 届く定義か節点
+    let mut uses: HashMap<(usize, usize), usize> = HashMap::new();
+    for &id in &order {
+        let mut current: HashMap<usize, usize> = HashMap::new();
+        for (offset, opcode) in cfg.blocks[id].code.iter().enumerate() {
+            let index = block_start[id] + offset;
+            for (n, src) in opcode.srcs().into_iter().enumerate() {
+                let def = current.get(&src.id).or_else(|| joins[id].get(&src.id)).cloned();
+                uses.insert((index, n), def.unwrap_or_else(|| entry_def(src.id)));
+            }
+            if let Some(dst) = opcode.dst() {
+                current.insert(dst.id, index);
+            }
+        }
+    }
+
+    // 番号を付け直す
+    let mut names: HashMap<usize, usize> = HashMap::new();
+    let mut original = BTreeMap::new();
+    let mut named = BitSet::new(register_num);
+    let mut next_id = register_num;
+    let mut name = |classes: &mut Classes, def: usize, reg_id: usize| {
+        let class = classes.find(def);
+        *names.entry(class).or_insert_with(|| {
+            let id = if named.insert(reg_id) {
+                reg_id
+            } else {
+                next_id += 1;
+                next_id - 1
+            };
+            original.insert(id, reg_id);
+            id
+        })
+    };
+
+    let mut code = Vec::with_capacity(len);
+    for (index, opcode) in opcodes.iter().enumerate() {
+        let mut n = 0;
+        let srcs: Vec<Register> = opcode.srcs().into_iter().map(|src| {
+            // 到達できない命令の使用は入口の値とみなす
+            let def = uses.get(&(index, n)).cloned().unwrap_or_else(|| entry_def(src.id));
+            n += 1;
+            Register::new(name(&mut classes, def, src.id))
+        }).collect();
+        let dst = opcode.dst().map(|dst| Register::new(name(&mut classes, index, dst.id)));
+
+        let mut srcs = srcs.into_iter();
+        code.push(opcode.map_operands(|_| srcs.next().unwrap(), |_| dst.clone().unwrap()));
+    }
+
+    Webs { code, original }
+}
@@ -0,0 +1,116 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, AllocError, Registry, MIN_REGISTER_NUM};
+use compiler_practice::ir::Label;
+use compiler_practice::parser::parse;
+use compiler_practice::{Integer, OpeCode, Register};
+
+const SOURCE: &str = "
+loadi %1, 1
+loadi %2, 2
+add %3, %1, %2
+print %3
+";
+
+// すべての割り当て方法でregister_num個のレジスタに割り当てたときのエラー
+fn errors(source: &str, register_num: usize) -> Vec<(&'static str, AllocError)> {
+    let opcodes = parse(source).unwrap();
+    Registry::default().create_all(&AllocConfig::new(register_num)).into_iter()
+        .map(|allocator| (allocator.name(), allocator.allocate(&opcodes).unwrap_err()))
+        .collect()
+}
+
+#[test]
+fn too_few_registers() {
+    let opcodes = parse(SOURCE).unwrap();
+    for allocator in Registry::default().create_all(&AllocConfig::new(2)) {
+        // chaitinとbriggsは一時レジスタを取っておかないので2つで足りる
+        if allocator.min_register_num() <= 2 {
+            assert!(allocator.allocate(&opcodes).is_ok(), "{}", allocator.name());
+            continue;
+        }
+        let err = allocator.allocate(&opcodes).unwrap_err();
+        assert_eq!(err, AllocError::TooFewRegisters { required: MIN_REGISTER_NUM, available: 2 }, "{}", allocator.name());
+        assert_eq!(err.to_string(), "at least 3 registers are required but only 2 available");
+    }
+
+    for (name, err) in errors(SOURCE, 1) {
+        assert!(matches!(err, AllocError::TooFewRegisters { available: 1, .. }), "{}: {:?}", name, err);
+    }
+}
+
+#[test]
+fn empty_program() {
+    for source in &["", "; comment only\n"] {
+        for (name, err) in errors(source, MIN_REGISTER_NUM) {
+            assert_eq!(err, AllocError::EmptyProgram, "{}", name);
+            assert_eq!(err.to_string(), "program is empty");
+        }
+    }
+}
+
+#[test]
+fn undefined_register() {
+    let source = "
+loadi %1, 1
+add %3, %1, %2
+loadi %2, 2
+print %3
+";
+    for (name, err) in errors(source, MIN_REGISTER_NUM) {
+        assert_eq!(err, AllocError::UndefinedRegister { reg: 2, index: 1 }, "{}", name);
+        assert_eq!(err.to_string(), "instruction 1 reads %2 before it is defined");
+    }
+}
+
+// パーサーを通さないと未定義のラベルに飛べる
+#[test]
+fn undefined_label() {
+    let opcodes = vec![
+        OpeCode::LdI { dst: Register::new(1), value: Integer::new(0) },
+        OpeCode::Label { label: Label::new("loop") },
+        OpeCode::Bne { src1: Register::new(1), src2: Register::new(1), target: Label::new("loop") },
+        OpeCode::Jmp { target: Label::new("end") },
+    ];
+    for allocator in Registry::default().create_all(&AllocConfig::new(MIN_REGISTER_NUM)) {
+        let err = allocator.allocate(&opcodes).unwrap_err();
+        assert_eq!(err, AllocError::UndefinedLabel { label: "end".to_string(), index: 3 }, "{}", allocator.name());
+        assert_eq!(err.to_string(), "instruction 3 jumps to undefined label `end`");
+    }
+}
+
+#[test]
+fn no_room_for_spill_slots() {
+    let source = "
+loadi %1, 1
+loadi %2, 2
+add %3, %1, %2
+store 2147483647, %3
+print %3
+";
+    let opcodes = parse(source).unwrap();
+    let registry = Registry::default();
+    let naive = registry.create("naive", AllocConfig::new(3)).unwrap();
+    let err = naive.allocate(&opcodes).unwrap_err();
+    assert_eq!(err, AllocError::NoRoomForSpillSlots { base: 2147483648, slots: 2 });
+    assert_eq!(err.to_string(), "no room for spill slots: 2 slot(s) from address 2147483648 do not fit in memory");
+
+    // spillしなければプログラムが使うアドレスはどこでもよい
+    assert!(registry.create("naive", AllocConfig::new(5)).unwrap().allocate(&opcodes).is_ok());
+
+    // VMのメモリの最後のワードを使っている
+    let opcodes = parse(&source.replace("2147483647", "1023")).unwrap();
+    assert_eq!(naive.allocate(&opcodes).unwrap_err(), AllocError::NoRoomForSpillSlots { base: 1024, slots: 2 });
+    for allocator in registry.create_all(&AllocConfig::new(2)) {
+        if let Err(err) = allocator.allocate(&opcodes) {
+            assert!(matches!(err, AllocError::NoRoomForSpillSlots { base: 1024, .. } | AllocError::TooFewRegisters { .. }), "{}: {:?}", allocator.name(), err);
+        }
+    }
+}
+
+// 割り当て方法の内部で不変条件が崩れたときだけ返すので、入力からは作れない
+#[test]
+fn uncolorable() {
+    let err: Box<dyn std::error::Error> = Box::new(AllocError::Uncolorable);
+    assert_eq!(err.to_string(), "interference graph cannot be colored");
+}
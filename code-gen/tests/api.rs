@@ -0,0 +1,49 @@
+extern crate compiler_practice;
+
+use compiler_practice::ir::max_register_id;
+use compiler_practice::parser::parse;
+use compiler_practice::printer::Listing;
+use compiler_practice::vm::VmConfig;
+use compiler_practice::{allocate_registers1, allocate_registers2, allocate_registers_coalescing, allocate_registers_linear_scan};
+use compiler_practice::{run_vm, AllocError, Integer, OpeCode, Register};
+
+type Allocate = fn(Vec<OpeCode>, usize) -> Result<Vec<OpeCode>, AllocError>;
+
+// %1 + %2 + %3 + %4を出力する
+fn program() -> Vec<OpeCode> {
+    let mut opcodes: Vec<OpeCode> = (1..5).map(|id| OpeCode::LdI { dst: Register::new(id), value: Integer::new(id as i32) }).collect();
+    opcodes.push(OpeCode::Add { dst: Register::new(5), src1: Register::new(1), src2: Register::new(2) });
+    opcodes.push(OpeCode::Add { dst: Register::new(6), src1: Register::new(5), src2: Register::new(3) });
+    opcodes.push(OpeCode::Add { dst: Register::new(7), src1: Register::new(6), src2: Register::new(4) });
+    opcodes.push(OpeCode::Print { src: Register::new(7) });
+    opcodes
+}
+
+#[test]
+fn build_and_print() {
+    let opcodes = program();
+    assert_eq!(max_register_id(&opcodes), 7);
+    assert_eq!(opcodes[4].to_string(), "add %5, %1, %2");
+
+    // 出力したものをパースすると元に戻る
+    assert_eq!(parse(&Listing::new(&opcodes).to_string()).unwrap(), opcodes);
+    assert_eq!(parse(include_str!("../source.s")).unwrap(), opcodes);
+}
+
+#[test]
+fn allocate_functions() {
+    let functions: [(&str, Allocate); 4] = [
+        ("naive", allocate_registers1),
+        ("chaitin", allocate_registers2),
+        ("irc", allocate_registers_coalescing),
+        ("linear-scan", allocate_registers_linear_scan),
+    ];
+    for &(name, allocate) in &functions {
+        for register_num in 3..8 {
+            let code = allocate(program(), register_num).unwrap();
+            assert!(max_register_id(&code) <= register_num, "{} --regs {}", name, register_num);
+            assert_eq!(run_vm(&code, &VmConfig::new(register_num)).unwrap().output, vec![10], "{} --regs {}", name, register_num);
+        }
+        assert_eq!(allocate(Vec::new(), 4), Err(AllocError::EmptyProgram), "{}", name);
+    }
+}
@@ -0,0 +1,118 @@
+extern crate compiler_practice;
+
+use compiler_practice::cfg::Cfg;
+use compiler_practice::gen::{generate, GenConfig};
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::OpeCode;
+
+fn output(opcodes: &[OpeCode]) -> Vec<i32> {
+    run_vm(opcodes, &VmConfig::new(16)).unwrap().output
+}
+
+// 0: entry, 1: loadi, 2: top (cond), 3: then, 4: join (loop back), 5: print, 6: exit
+const DIAMOND: &str = "
+loadi %1, 0
+loadi %2, 1
+loadi %3, 3
+top:
+blt %1, %2, join
+add %1, %1, %2
+join:
+add %1, %1, %2
+blt %1, %3, top
+print %1
+";
+
+#[test]
+fn blocks_and_edges() {
+    let cfg = Cfg::new(&parse(DIAMOND).unwrap());
+    assert_eq!(cfg.blocks.len(), 7);
+    assert_eq!((cfg.entry, cfg.exit), (0, 6));
+
+    let succs: Vec<Vec<usize>> = cfg.blocks.iter().map(|block| block.succs.clone()).collect();
+    assert_eq!(succs, vec![vec![1], vec![2], vec![3, 4], vec![4], vec![5, 2], vec![6], vec![]]);
+    assert_eq!(cfg.blocks[2].preds, vec![1, 4]);
+    assert_eq!(cfg.blocks[4].preds, vec![2, 3]);
+
+    assert_eq!(cfg.postorder(), vec![6, 5, 4, 3, 2, 1, 0]);
+    assert_eq!(cfg.reverse_postorder(), vec![0, 1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn straight_line_round_trip() {
+    for seed in 0..20 {
+        let opcodes = generate(seed, &GenConfig::default());
+        let cfg = Cfg::new(&opcodes);
+        assert_eq!(cfg.blocks.len(), 3);
+        assert_eq!(cfg.to_opcodes(), opcodes);
+    }
+}
+
+#[test]
+fn remove_unreachable() {
+    let opcodes = parse("loadi %1, 1\njmp end\ndead:\nprint %1\njmp dead\nend:\nprint %1\n").unwrap();
+    let mut cfg = Cfg::new(&opcodes);
+    assert_eq!(cfg.blocks.len(), 5);
+
+    cfg.remove_unreachable();
+    assert_eq!(cfg.blocks.len(), 4);
+    assert!(cfg.blocks.iter().all(|block| block.label().is_none_or(|label| label.name != "dead")));
+    assert_eq!(cfg.blocks[cfg.exit].preds, vec![2]);
+    assert_eq!(output(&cfg.to_opcodes()), vec![1]);
+}
+
+#[test]
+fn split_critical_edges() {
+    let opcodes = parse(DIAMOND).unwrap();
+    let mut cfg = Cfg::new(&opcodes);
+    cfg.split_critical_edges();
+
+    // 2 -> 4 と 4 -> 2 が分けられる
+    assert_eq!(cfg.blocks.len(), 9);
+    for block in &cfg.blocks {
+        if block.succs.len() > 1 {
+            assert!(block.succs.iter().all(|&succ| cfg.blocks[succ].preds.len() == 1));
+        }
+    }
+    assert_eq!(output(&cfg.to_opcodes()), output(&opcodes));
+    assert_eq!(output(&Cfg::new(&cfg.to_opcodes()).to_opcodes()), output(&opcodes));
+}
+
+#[test]
+fn loop_program() {
+    let opcodes = parse(include_str!("../loop.s")).unwrap();
+    let mut cfg = Cfg::new(&opcodes);
+    assert_eq!(cfg.to_opcodes(), opcodes);
+
+    cfg.split_critical_edges();
+    cfg.remove_unreachable();
+    assert_eq!(output(&cfg.to_opcodes()), vec![55, 25]);
+}
+
+#[test]
+fn loop_depths() {
+    let source = "
+loadi %1, 0
+loadi %2, 1
+loadi %3, 3
+outer:
+loadi %4, 0
+inner:
+add %4, %4, %2
+blt %4, %3, inner
+add %1, %1, %2
+blt %1, %3, outer
+print %1
+";
+    let cfg = Cfg::new(&parse(source).unwrap());
+    let depths: Vec<(usize, usize)> = cfg.blocks.iter().zip(cfg.loop_depths())
+        .filter(|&(block, _)| !block.code.is_empty())
+        .map(|(block, depth)| (block.code.len(), depth))
+        .collect();
+    // (命令数, 深さ): 入口, outer, inner, outerの残り, 出口の前
+    assert_eq!(depths, vec![(3, 0), (2, 1), (3, 2), (2, 1), (1, 0)]);
+
+    let depths = Cfg::new(&parse(DIAMOND).unwrap()).loop_depths();
+    assert_eq!(depths, vec![0, 0, 1, 1, 1, 0, 0]);
+}
@@ -0,0 +1,118 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, Allocation, Chaitin, Coloring, IteratedCoalescing, LinearScan, Naive, RegisterAllocator, MIN_REGISTER_NUM};
+use compiler_practice::checker::{check_allocation, Violation};
+use compiler_practice::parser::parse;
+use compiler_practice::{Integer, OpeCode, Register};
+
+fn allocate(allocator: &dyn RegisterAllocator, source: &str) -> (Vec<OpeCode>, Allocation) {
+    let opcodes = parse(source).unwrap();
+    let allocation = allocator.allocate(&opcodes).unwrap();
+    (opcodes, allocation)
+}
+
+#[test]
+fn accepts_allocators() {
+    for source in &[include_str!("../source.s"), include_str!("../dest.s"), include_str!("../parallel.s")] {
+        for register_num in MIN_REGISTER_NUM..10 {
+            let config = AllocConfig::new(register_num);
+            let allocators: Vec<Box<dyn RegisterAllocator>> = vec![
+                Box::new(Naive::new(config.clone())),
+                Box::new(Chaitin::new(config.clone())),
+                Box::new(Chaitin::with_coloring(config.clone(), Coloring::Optimistic)),
+                Box::new(LinearScan::new(config.clone())),
+                Box::new(IteratedCoalescing::new(config)),
+            ];
+            for allocator in allocators {
+                let (opcodes, allocation) = allocate(&*allocator, source);
+                assert_eq!(check_allocation(&opcodes, &allocation, register_num), vec![], "{} --regs {}", allocator.name(), register_num);
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_shared_register() {
+    let source = "loadi %1, 1\nloadi %2, 2\nadd %3, %1, %2\nprint %3\n";
+    let (opcodes, mut allocation) = allocate(&Chaitin::new(AllocConfig::new(5)), source);
+    let phys = allocation.assignment[&1];
+    allocation.assignment.insert(2, phys);
+    allocation.code = opcodes.iter().map(|op| op.map_registers(|reg| Register::new(allocation.assignment[&reg.id]))).collect();
+
+    let violations = check_allocation(&opcodes, &allocation, 5);
+    assert!(violations.iter().any(|v| matches!(*v, Violation::Interference { reg1: 1, reg2: 2, .. })), "{:?}", violations);
+    assert!(violations.iter().any(|v| matches!(*v, Violation::Clobbered { index: 2, reg: 1, .. })), "{:?}", violations);
+}
+
+#[test]
+fn rejects_out_of_budget() {
+    let (opcodes, mut allocation) = allocate(&Naive::new(AllocConfig::new(4)), "loadi %1, 1\nprint %1\n");
+    allocation.code[1] = OpeCode::Print { src: Register::new(5) };
+
+    let violations = check_allocation(&opcodes, &allocation, 4);
+    assert_eq!(violations[0], Violation::OutOfBudget { index: 1, reg: 5 });
+}
+
+#[test]
+fn rejects_bad_reloads() {
+    // %2と%3がspillされる
+    let source = "loadi %1, 1\nloadi %2, 2\nloadi %3, 3\nprint %1\nprint %2\nprint %3\n";
+    let (opcodes, allocation) = allocate(&Naive::new(AllocConfig::new(3)), source);
+    let slot2 = allocation.slots[&2];
+    let slot3 = allocation.slots[&3];
+    let is_reload = |op: &OpeCode, slot: i32| matches!(*op, OpeCode::Load { ref src, .. } if src.value == slot);
+
+    // 最初のStoreを消すと、Storeする前にLoadすることになる
+    let mut before_store = allocation.clone();
+    let first_store = before_store.code.iter().position(|op| matches!(*op, OpeCode::Store { .. })).unwrap();
+    before_store.code.remove(first_store);
+    let violations = check_allocation(&opcodes, &before_store, 3);
+    assert!(violations.iter().any(|v| matches!(*v, Violation::ReloadBeforeStore { addr, .. } if addr == slot2)), "{:?}", violations);
+
+    // %2を読むときに%3の置き場所からLoadする
+    let mut wrong_slot = allocation.clone();
+    let reload = wrong_slot.code.iter().position(|op| is_reload(op, slot2)).unwrap();
+    if let OpeCode::Load { ref mut src, .. } = wrong_slot.code[reload] {
+        *src = Integer::new(slot3);
+    }
+    let violations = check_allocation(&opcodes, &wrong_slot, 3);
+    assert!(violations.iter().any(|v| matches!(*v, Violation::WrongSlot { reg: 2, addr, .. } if addr == slot3)), "{:?}", violations);
+}
+
+#[test]
+fn accepts_moves_folded_into_spill_code() {
+    // %1と%5がspillされ、mov %5, %1は一時レジスタを通したLoadとStoreだけになる
+    let source = "
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+loadi %4, 4
+print %2
+print %3
+print %4
+mov %5, %1
+print %2
+print %3
+print %4
+print %5
+";
+    for &coloring in &[Coloring::Pessimistic, Coloring::Optimistic] {
+        let (opcodes, allocation) = allocate(&Chaitin::with_coloring(AllocConfig::new(3), coloring), source);
+        assert!(allocation.code.iter().all(|op| op.as_move().is_none()));
+        assert_eq!(check_allocation(&opcodes, &allocation, 3), vec![]);
+
+        // 読み込み元とは別の置き場所からLoadすると、消えたMovと対応しなくなる
+        let (dst, src) = allocation.ranges.iter().find_map(|op| op.as_move()).unwrap();
+        let (from, to) = (allocation.slots[&src.id], allocation.slots[&dst.id]);
+        let folded = allocation.code.windows(2).position(|ops| {
+            matches!((&ops[0], &ops[1]), (OpeCode::Load { src, .. }, OpeCode::Store { dst, .. }) if src.value == from && dst.value == to)
+        }).unwrap();
+        let other = *allocation.slots.values().find(|&&addr| addr != from && addr != to).unwrap();
+        let mut wrong_slot = allocation.clone();
+        if let OpeCode::Load { ref mut src, .. } = wrong_slot.code[folded] {
+            *src = Integer::new(other);
+        }
+        let violations = check_allocation(&opcodes, &wrong_slot, 3);
+        assert!(matches!(violations[..], [Violation::Unaligned { .. }]), "{:?}", violations);
+    }
+}
@@ -0,0 +1,112 @@
+extern crate compiler_practice;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+
+const SOURCE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/source.s");
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_compiler-practice")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+// テストごとに別の一時ファイル
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("compiler-practice-{}-{}", process::id(), name))
+}
+
+#[test]
+fn alloc() {
+    let output = run(&["alloc", "--algo", "chaitin", "--regs", "4", SOURCE]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(text.lines().next().unwrap().ends_with("; chaitin --regs 4"), "{}", text);
+    let opcodes = parse(&text).unwrap();
+    assert_eq!(run_vm(&opcodes, &VmConfig::new(4)).unwrap().output, vec![10]);
+
+    // -oに書くと標準出力には何も出さない
+    let path = temp_path("alloc.s");
+    let output = run(&["alloc", "--algo", "naive", "--regs", "4", "-o", path.to_str().unwrap(), SOURCE]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(output.stdout.is_empty());
+    let opcodes = parse(&fs::read_to_string(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(run_vm(&opcodes, &VmConfig::new(4)).unwrap().output, vec![10]);
+}
+
+#[test]
+fn alloc_failure() {
+    let output = run(&["alloc", "--algo", "naive", "--regs", "2", SOURCE]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), format!("error: {}: at least 3 registers are required but only 2 available\n", SOURCE));
+
+    let output = run(&["alloc", "--algo", "naive", "--regs", "4", "missing.s"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("error: missing.s: "));
+}
+
+#[test]
+fn usage_errors() {
+    for &(args, message) in &[
+        (&[][..], "no subcommand given"),
+        (&["compile", SOURCE][..], "unknown subcommand `compile`"),
+        (&["alloc", "--regs", "4", SOURCE][..], "`alloc` needs `--algo`"),
+        (&["alloc", "--algo", "greedy", "--regs", "4", SOURCE][..], "unknown algorithm `greedy`"),
+        (&["alloc", "--algo", "naive", "--regs", "four", SOURCE][..], "bad register number `four`"),
+        (&["run", "--algo", "naive", SOURCE][..], "`run` does not take `--algo`"),
+        (&["bench", "--regs", "5..5", SOURCE][..], "empty register range `5..5`"),
+        (&["fuzz", "--seed", "18446744073709551615", "--iterations", "2"][..],
+         "seeds from 18446744073709551615 for 2 iteration(s) do not fit in u64"),
+    ] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let text = stderr(&output);
+        assert!(text.starts_with(&format!("error: {}\nusage:", message)), "{:?}\n{}", args, text);
+        assert!(text.ends_with("algorithms: naive, chaitin, briggs, linear-scan, irc\n"), "{}", text);
+    }
+}
+
+#[test]
+fn run_program() {
+    let output = run(&["run", SOURCE]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "10\n");
+
+    let output = run(&["run", "--dump", SOURCE]);
+    assert!(stdout(&output).starts_with("10\n\nregisters\n  %1 = 1\n"), "{}", stdout(&output));
+
+    let output = run(&["run", "--regs", "3", SOURCE]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), format!("error: {}: instruction 3: register %4 is out of range\n", SOURCE));
+}
+
+#[test]
+fn bench() {
+    let output = run(&["bench", "--regs", "3..5", SOURCE]);
+    assert_eq!(output.status.code(), Some(0));
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "reg num, naive, chaitin, briggs, linear-scan, irc");
+    assert_eq!(lines.len(), 3);
+    for (line, register_num) in lines[1..].iter().zip(3..) {
+        let columns: Vec<&str> = line.split(", ").collect();
+        assert_eq!(columns[0], register_num.to_string());
+        assert!(columns[1..].iter().all(|count| count.parse::<usize>().is_ok()), "{}", line);
+    }
+
+    // 割り当てられなければ"-"
+    let output = run(&["bench", "--regs", "2", SOURCE]);
+    assert_eq!(stdout(&output).lines().nth(1), Some("2, -, 14, 14, -, -"));
+}
@@ -0,0 +1,109 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, IteratedCoalescing, LowestNumber, RegisterAllocator};
+use compiler_practice::checker::check_allocation;
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::OpeCode;
+
+fn moves(code: &[OpeCode]) -> usize {
+    code.iter().filter(|op| op.as_move().is_some()).count()
+}
+
+// コピーした値を使い続ける
+const CHAIN: &str = "
+loadi %1, 1
+loadi %2, 2
+mov %3, %1
+add %4, %3, %2
+mov %5, %4
+mov %6, %5
+add %7, %6, %3
+print %7
+";
+
+#[test]
+fn removes_coalesced_moves() {
+    let opcodes = parse(CHAIN).unwrap();
+    let allocation = IteratedCoalescing::new(AllocConfig::new(4)).allocate(&opcodes).unwrap();
+    assert_eq!(moves(&allocation.code), 0, "{:?}", allocation.code);
+    assert!(allocation.spilled.is_empty());
+    assert_eq!(allocation.assignment[&1], allocation.assignment[&3]);
+    assert_eq!(allocation.assignment[&4], allocation.assignment[&6]);
+    assert_eq!(check_allocation(&opcodes, &allocation, 4), vec![]);
+
+    let result = run_vm(&allocation.code, &VmConfig::new(4)).unwrap();
+    assert_eq!(result.output, vec![4]);
+}
+
+#[test]
+fn keeps_constrained_moves() {
+    // %2にコピーしたあとも%1を書き換えるので、%1と%2は同じレジスタにできない
+    let source = "
+loadi %1, 0
+loadi %3, 1
+loadi %5, 3
+loop:
+mov %2, %1
+add %1, %1, %3
+blt %1, %5, loop
+print %2
+print %1
+";
+    let opcodes = parse(source).unwrap();
+    let allocation = IteratedCoalescing::new(AllocConfig::new(6)).allocate(&opcodes).unwrap();
+    assert_eq!(moves(&allocation.code), 1);
+    assert_ne!(allocation.assignment[&1], allocation.assignment[&2]);
+
+    let result = run_vm(&allocation.code, &VmConfig::new(6)).unwrap();
+    assert_eq!(result.output, vec![2, 3]);
+}
+
+#[test]
+fn loop_with_copies() {
+    let source = "
+loadi %1, 0
+loadi %2, 1
+loadi %3, 5
+loop:
+add %4, %1, %2
+mov %1, %4
+blt %1, %3, loop
+print %1
+";
+    let opcodes = parse(source).unwrap();
+    for register_num in 3..7 {
+        let allocation = IteratedCoalescing::new(AllocConfig::new(register_num)).allocate(&opcodes).unwrap();
+        let result = run_vm(&allocation.code, &VmConfig::new(register_num)).unwrap();
+        assert_eq!(result.output, vec![5], "--regs {}", register_num);
+        if register_num >= 5 {
+            assert_eq!(moves(&allocation.code), 0, "--regs {}", register_num);
+        }
+    }
+}
+
+// ループの中で3つの値が生きている。%4はループの外でしか使わない
+const OUTSIDE_LOOP: &str = "
+loadi %1, 0
+loadi %2, 1
+loadi %3, 5
+loadi %4, 7
+loop:
+add %1, %1, %2
+blt %1, %3, loop
+print %1
+print %4
+";
+
+#[test]
+fn spills_values_outside_loops() {
+    let opcodes = parse(OUTSIDE_LOOP).unwrap();
+    let allocation = IteratedCoalescing::new(AllocConfig::new(5)).allocate(&opcodes).unwrap();
+    assert_eq!(allocation.spilled.iter().cloned().collect::<Vec<_>>(), vec![4]);
+    assert_eq!(run_vm(&allocation.code, &VmConfig::new(5)).unwrap().output, vec![5, 7]);
+
+    // 番号だけで選ぶとループの中で使う値からspillする
+    let allocation = IteratedCoalescing::new(AllocConfig::new(5)).with_spill_heuristic(Box::new(LowestNumber)).allocate(&opcodes).unwrap();
+    assert_eq!(allocation.spilled.iter().cloned().collect::<Vec<_>>(), vec![1]);
+    assert_eq!(run_vm(&allocation.code, &VmConfig::new(5)).unwrap().output, vec![5, 7]);
+}
@@ -0,0 +1,113 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, AllocError, Chaitin, Coloring, LowestNumber, Registry, RegisterAllocator, MIN_REGISTER_NUM};
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::OpeCode;
+
+const CORPUS: &[&str] = &[
+    include_str!("../source.s"),
+    include_str!("../dest.s"),
+    include_str!("../parallel.s"),
+    include_str!("../loop.s"),
+];
+
+// spillの選び方を揃えて、彩色の違いだけを比べる
+#[test]
+fn optimistic_coloring_saves_spills() {
+    let (mut pessimistic, mut optimistic) = (0, 0);
+    for source in CORPUS {
+        let opcodes = parse(source).unwrap();
+        for register_num in MIN_REGISTER_NUM..10 {
+            let config = AllocConfig::new(register_num);
+            let chaitin = Chaitin::new(config.clone()).with_spill_heuristic(Box::new(LowestNumber)).allocate(&opcodes).unwrap();
+            let briggs = Chaitin::with_coloring(config, Coloring::Optimistic).with_spill_heuristic(Box::new(LowestNumber))
+                .allocate(&opcodes).unwrap();
+            assert!(briggs.spilled.len() <= chaitin.spilled.len(), "--regs {}\n{}", register_num, source);
+
+            let config = VmConfig::new(register_num);
+            assert_eq!(run_vm(&briggs.code, &config).unwrap().output, run_vm(&chaitin.code, &config).unwrap().output);
+
+            pessimistic += chaitin.spilled.len();
+            optimistic += briggs.spilled.len();
+        }
+    }
+    assert!(optimistic < pessimistic, "{} < {}", optimistic, pessimistic);
+}
+
+#[test]
+fn names() {
+    let config = AllocConfig::new(4);
+    assert_eq!(Chaitin::new(config.clone()).name(), "chaitin");
+    assert_eq!(Chaitin::with_coloring(config, Coloring::Optimistic).name(), "briggs");
+}
+
+// 4つの値が同時に生きている
+const FOUR_LIVE: &str = "
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+loadi %4, 4
+print %1
+print %2
+print %3
+print %4
+";
+
+#[test]
+fn no_reserved_registers() {
+    let opcodes = parse(FOUR_LIVE).unwrap();
+    for &coloring in &[Coloring::Pessimistic, Coloring::Optimistic] {
+        let allocation = Chaitin::with_coloring(AllocConfig::new(4), coloring).allocate(&opcodes).unwrap();
+        assert!(allocation.spilled.is_empty());
+        assert_eq!(allocation.code.len(), opcodes.len());
+        assert_eq!(run_vm(&allocation.code, &VmConfig::new(4)).unwrap().output, vec![1, 2, 3, 4]);
+    }
+}
+
+// 一時レジスタを取っておかないので、2つあれば割り当てられる
+#[test]
+fn two_registers() {
+    let opcodes = parse(FOUR_LIVE).unwrap();
+    for allocator in Registry::default().create_all(&AllocConfig::new(2)) {
+        let colors = allocator.name() == "chaitin" || allocator.name() == "briggs";
+        assert_eq!(allocator.min_register_num(), if colors { 2 } else { MIN_REGISTER_NUM }, "{}", allocator.name());
+        assert_eq!(allocator.allocate(&opcodes).is_ok(), colors, "{}", allocator.name());
+    }
+    for source in CORPUS {
+        let opcodes = parse(source).unwrap();
+        for &coloring in &[Coloring::Pessimistic, Coloring::Optimistic] {
+            let allocation = Chaitin::with_coloring(AllocConfig::new(2), coloring).allocate(&opcodes).unwrap();
+            assert_eq!(run_vm(&allocation.code, &VmConfig::new(2)).unwrap().output, run_vm(&opcodes, &VmConfig::new(16)).unwrap().output, "{}", source);
+        }
+        let err = Chaitin::new(AllocConfig::new(1)).allocate(&opcodes).unwrap_err();
+        assert_eq!(err, AllocError::TooFewRegisters { required: 2, available: 1 });
+    }
+}
+
+// spillした値は読む直前にLoadし、書いた直後にStoreする
+#[test]
+fn short_lived_spill_temporaries() {
+    for source in CORPUS {
+        let opcodes = parse(source).unwrap();
+        for register_num in MIN_REGISTER_NUM..6 {
+            let allocation = Chaitin::new(AllocConfig::new(register_num)).allocate(&opcodes).unwrap();
+            let addrs: Vec<i32> = allocation.slots.values().cloned().collect();
+            let code = &allocation.code;
+            for (index, opcode) in code.iter().enumerate() {
+                match *opcode {
+                    OpeCode::Load { ref dst, ref src } if addrs.contains(&src.value) => {
+                        let next = code[index + 1..].iter().find(|op| !matches!(**op, OpeCode::Load { .. })).unwrap();
+                        assert!(next.srcs().contains(&dst), "--regs {}: {}\n{}", register_num, index, source);
+                    },
+                    OpeCode::Store { ref dst, ref src } if addrs.contains(&dst.value) => {
+                        assert_eq!(code[index - 1].dst(), Some(src), "--regs {}: {}\n{}", register_num, index, source);
+                    },
+                    _ => {},
+                }
+            }
+            let config = VmConfig::new(register_num);
+            assert_eq!(run_vm(&allocation.code, &config).unwrap().output, run_vm(&opcodes, &VmConfig::new(16)).unwrap().output);
+        }
+    }
+}
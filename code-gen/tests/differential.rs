@@ -0,0 +1,101 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, AllocError, Registry, MIN_REGISTER_NUM};
+use compiler_practice::differential::compare_all;
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+
+// 10個の値を最後まで生かしておき、メモリも使う
+const PRESSURE: &str = "
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+loadi %4, 4
+loadi %5, 5
+loadi %6, 6
+loadi %7, 7
+loadi %8, 8
+loadi %9, 9
+loadi %10, 10
+add %11, %1, %2
+add %12, %11, %3
+add %13, %12, %4
+add %14, %13, %5
+store 0, %14
+add %15, %14, %6
+add %16, %15, %7
+load %17, 0
+add %18, %16, %17
+print %1
+print %2
+print %3
+print %4
+print %5
+print %6
+print %7
+print %8
+print %9
+print %10
+print %18
+";
+
+fn check(source: &str) {
+    let opcodes = parse(source).unwrap();
+    let mismatches = compare_all(&opcodes, &Registry::default(), MIN_REGISTER_NUM..16);
+    let messages: Vec<String> = mismatches.iter().map(|mismatch| mismatch.to_string()).collect();
+    assert!(messages.is_empty(), "{}", messages.join("\n"));
+}
+
+#[test]
+fn source() {
+    check(include_str!("../source.s"));
+}
+
+#[test]
+fn dest() {
+    check(include_str!("../dest.s"));
+}
+
+#[test]
+fn parallel() {
+    check(include_str!("../parallel.s"));
+}
+
+#[test]
+fn pressure() {
+    check(PRESSURE);
+}
+
+#[test]
+fn control_flow() {
+    check(include_str!("../loop.s"));
+}
+
+// %2はファイルの順では読むほうが先だが、実行の順では書き込みが先
+const DEFINED_LATER: &str = "
+loadi %1, 1
+jmp b
+a:
+print %2
+jmp end
+b:
+loadi %2, 5
+jmp a
+end:
+print %1
+";
+
+#[test]
+fn defined_later_in_file() {
+    let opcodes = parse(DEFINED_LATER).unwrap();
+    assert_eq!(run_vm(&opcodes, &VmConfig::new(2)).unwrap().output, vec![5, 1]);
+    check(DEFINED_LATER);
+}
+
+#[test]
+fn undefined_on_some_path() {
+    let opcodes = parse("loadi %1, 1\nbeq %1, %1, skip\nloadi %2, 2\nskip:\nprint %2\n").unwrap();
+    for allocator in Registry::default().create_all(&AllocConfig::new(4)) {
+        assert_eq!(allocator.allocate(&opcodes).unwrap_err(), AllocError::UndefinedRegister { reg: 2, index: 4 });
+    }
+}
@@ -0,0 +1,44 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{Registry, MIN_REGISTER_NUM};
+use compiler_practice::fuzz::fuzz;
+use compiler_practice::gen::{GenConfig, Mix};
+use compiler_practice::printer::Listing;
+
+fn check(config: GenConfig) {
+    let failures = fuzz(0..50, &config, &Registry::default(), MIN_REGISTER_NUM..9);
+    if let Some(failure) = failures.first() {
+        panic!("seed {}: {}\n{}", failure.seed, failure.failure, Listing::new(&failure.opcodes));
+    }
+}
+
+#[test]
+fn default_programs() {
+    check(GenConfig::default());
+}
+
+#[test]
+fn high_pressure() {
+    check(GenConfig { length: 60, registers: 32, live_values: 16, ..GenConfig::default() });
+}
+
+#[test]
+fn few_registers_reused() {
+    check(GenConfig { registers: 4, live_values: 3, ..GenConfig::default() });
+}
+
+#[test]
+fn copy_heavy() {
+    check(GenConfig {
+        mix: Mix { ldi: 2, add: 2, store: 1, load: 1, print: 2, mov: 4 },
+        ..GenConfig::default()
+    });
+}
+
+#[test]
+fn memory_heavy() {
+    check(GenConfig {
+        mix: Mix { ldi: 2, add: 2, store: 3, load: 3, print: 2, mov: 1 },
+        ..GenConfig::default()
+    });
+}
@@ -0,0 +1,22 @@
+extern crate compiler_practice;
+
+use compiler_practice::gen::Rng;
+
+// どのシードでも0ばかり出ることはない
+#[test]
+fn seeds_never_stall() {
+    for &seed in &[0, 1, 0x9e37_79b9_7f4a_7c15, 0x61c8_8646_80b5_83eb, u64::MAX] {
+        let mut rng = Rng::new(seed);
+        let values: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        assert!(values.iter().all(|&value| value != 0), "seed {:#x}: {:?}", seed, values);
+    }
+}
+
+#[test]
+fn same_seed_same_sequence() {
+    let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+    assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+    let mut c = Rng::new(43);
+    assert_ne!(Rng::new(42).next_u64(), c.next_u64());
+    assert!((0..100).all(|_| c.below(7) < 7));
+}
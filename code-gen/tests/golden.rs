@@ -0,0 +1,130 @@
+//! tests以下の.sファイル (サブディレクトリも含む) を割り当てて、期待する出力と比べる
+//!
+//! 各ファイルの先頭のコメントで設定する
+//!
+//! ```text
+//! ; algo: chaitin
+//! ; regs: 4
+//! ; expect: source.s.expected
+//! ; output: 10
+//! ```
+//!
+//! `expect`は割り当て後のプログラム (ファイルからの相対パス)、
+//! `output`は省略可能で、割り当て後のプログラムがPrintする値
+//!
+//! `BLESS=1 cargo test --test golden`で期待する出力を作り直す
+
+extern crate compiler_practice;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use compiler_practice::alloc::{AllocConfig, Registry};
+use compiler_practice::parser::parse;
+use compiler_practice::printer::Listing;
+use compiler_practice::vm::{run_vm, VmConfig};
+
+struct Case {
+    algo: String,
+    register_num: usize,
+    expect: PathBuf,
+    output: Option<Vec<i32>>,
+}
+
+fn collect(dir: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(&path, paths);
+        } else if path.extension().is_some_and(|ext| ext == "s") {
+            paths.push(path);
+        }
+    }
+}
+
+fn read_case(path: &Path, source: &str) -> Result<Case, String> {
+    let mut algo = None;
+    let mut register_num = None;
+    let mut expect = None;
+    let mut output = None;
+
+    for line in source.lines().take_while(|line| line.starts_with(';')) {
+        let mut header = line[1..].splitn(2, ':');
+        let key = header.next().unwrap().trim();
+        let value = match header.next() {
+            Some(value) => value.trim(),
+            None => continue,
+        };
+        match key {
+            "algo" => algo = Some(value.to_string()),
+            "regs" => register_num = Some(value.parse().map_err(|_| format!("bad regs `{}`", value))?),
+            "expect" => expect = Some(path.parent().unwrap().join(value)),
+            "output" => {
+                let values: Result<Vec<i32>, _> = value.split_whitespace().map(|v| v.parse()).collect();
+                output = Some(values.map_err(|_| format!("bad output `{}`", value))?);
+            },
+            _ => {},
+        }
+    }
+
+    Ok(Case {
+        algo: algo.ok_or("no `algo` header")?,
+        register_num: register_num.ok_or("no `regs` header")?,
+        expect: expect.ok_or("no `expect` header")?,
+        output,
+    })
+}
+
+fn run_case(case: &Case, source: &str, bless: bool) -> Result<(), String> {
+    let opcodes = parse(source).map_err(|err| err.to_string())?;
+    let allocator = Registry::default().create(&case.algo, AllocConfig::new(case.register_num))
+        .ok_or_else(|| format!("unknown algorithm `{}`", case.algo))?;
+    let allocation = allocator.allocate(&opcodes).map_err(|err| err.to_string())?;
+
+    if let Some(ref output) = case.output {
+        let result = run_vm(&allocation.code, &VmConfig::new(case.register_num)).map_err(|err| err.to_string())?;
+        if &result.output != output {
+            return Err(format!("output {:?} but expected {:?}", result.output, output));
+        }
+    }
+
+    let actual = Listing::new(&allocation.code).to_string();
+    if bless {
+        return fs::write(&case.expect, actual).map_err(|err| err.to_string());
+    }
+
+    let expected = fs::read_to_string(&case.expect)
+        .map_err(|err| format!("{}: {} (run with BLESS=1 to create it)", case.expect.display(), err))?;
+    if expected != actual {
+        let line = expected.lines().zip(actual.lines()).take_while(|&(e, a)| e == a).count();
+        return Err(format!(
+            "differs from {} at line {}\n--- expected\n{}--- actual\n{}",
+            case.expect.display(), line + 1, expected, actual,
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn golden() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let bless = env::var_os("BLESS").is_some();
+
+    let mut paths = Vec::new();
+    collect(&root, &mut paths);
+    paths.sort();
+    assert!(!paths.is_empty(), "no cases in {}", root.display());
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let source = fs::read_to_string(path).unwrap();
+        let result = read_case(path, &source).and_then(|case| run_case(&case, &source, bless));
+        if let Err(message) = result {
+            failures.push(format!("{}: {}", path.display(), message));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
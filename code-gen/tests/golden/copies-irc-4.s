@@ -0,0 +1,14 @@
+; algo: irc
+; regs: 4
+; expect: copies-irc-4.s.expected
+; output: 4 3
+
+loadi %1, 1
+loadi %2, 2
+mov %3, %1
+add %4, %3, %2
+mov %5, %4
+mov %6, %5
+add %7, %6, %3
+print %7
+print %5
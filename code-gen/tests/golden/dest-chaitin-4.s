@@ -0,0 +1,15 @@
+; algo: chaitin
+; regs: 4
+; expect: dest-chaitin-4.s.expected
+; output: 10
+
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+loadi %4, 4
+
+add %5, %1, %2
+add %5, %5, %3
+add %5, %5, %4
+
+print %5
@@ -0,0 +1,19 @@
+; algo: naive
+; regs: 5
+; expect: parallel-naive-5.s.expected
+; output: 3 7 11
+
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+loadi %4, 4
+loadi %5, 5
+loadi %6, 6
+
+add %7, %1, %2
+add %8, %3, %4
+add %9, %5, %6
+
+print %7 ; => 3
+print %8 ; => 7
+print %9 ; => 11
@@ -0,0 +1,15 @@
+; algo: naive
+; regs: 4
+; expect: source-naive-4.s.expected
+; output: 10
+
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+loadi %4, 4
+
+add %5, %1, %2
+add %6, %5, %3
+add %7, %6, %4
+
+print %7
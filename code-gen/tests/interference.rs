@@ -0,0 +1,86 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::allocate_registers2;
+use compiler_practice::bitset::BitSet;
+use compiler_practice::interference::InterferenceGraph;
+use compiler_practice::ir::{Integer, OpeCode, Register};
+use compiler_practice::parser::parse;
+
+const PROGRAM: &str = "
+loadi %1, 1
+loadi %2, 2
+add %3, %1, %2
+loadi %4, 4
+add %5, %3, %4
+print %5
+print %1
+";
+
+#[test]
+fn edges_and_degrees() {
+    let opcodes = parse(PROGRAM).unwrap();
+    let mut graph = InterferenceGraph::build(&opcodes, &BitSet::new(6));
+    assert_eq!(graph.len(), 6);
+
+    // %1は最後まで生きている
+    for reg_id in 2..6 {
+        assert!(graph.interferes(1, reg_id));
+        assert!(graph.interferes(reg_id, 1));
+    }
+    assert!(graph.interferes(3, 4));
+    assert!(!graph.interferes(2, 4));
+    assert!(!graph.interferes(3, 5));
+    assert_eq!(graph.edge_count(), 5);
+    assert_eq!(graph.degree(1), 4);
+
+    let mut changed = graph.remove(1);
+    changed.sort();
+    assert_eq!(changed, vec![2, 3, 4, 5]);
+    assert!(graph.is_removed(1));
+    assert_eq!(graph.degree(3), 1);
+    assert_eq!(graph.degree(1), 4);
+    assert!(graph.remove(1).is_empty());
+    // 取り除いても辺は残る
+    assert!(graph.interferes(1, 3));
+    assert_eq!(graph.neighbors(1).len(), 4);
+}
+
+#[test]
+fn excluded_nodes_have_no_edges() {
+    let opcodes = parse(PROGRAM).unwrap();
+    let mut excluded = BitSet::new(6);
+    excluded.insert(1);
+    let graph = InterferenceGraph::build(&opcodes, &excluded);
+    assert_eq!(graph.degree(1), 0);
+    assert!(graph.neighbors(1).is_empty());
+    assert_eq!(graph.edge_count(), 1);
+}
+
+// 10万命令、常に16個の値が生きているプログラム
+fn large_program() -> Vec<OpeCode> {
+    let mut opcodes = Vec::new();
+    for reg_id in 1..17 {
+        opcodes.push(OpeCode::LdI { dst: Register::new(reg_id), value: Integer::new(reg_id as i32) });
+    }
+    for reg_id in 17..100_000 {
+        opcodes.push(OpeCode::Add { dst: Register::new(reg_id), src1: Register::new(reg_id - 1), src2: Register::new(reg_id - 16) });
+    }
+    for reg_id in 99_984..100_000 {
+        opcodes.push(OpeCode::Print { src: Register::new(reg_id) });
+    }
+    opcodes
+}
+
+#[test]
+fn allocate_large_program() {
+    let opcodes = large_program();
+    assert!(opcodes.len() > 100_000);
+
+    // 定義の直後に生きている値とだけ辺を張るので、辺の数は命令数に比例する
+    // (loadiでは1 + 2 + ... + 15本、addでは15本ずつ)
+    let graph = InterferenceGraph::build(&opcodes, &BitSet::new(100_000));
+    assert_eq!(graph.edge_count(), 120 + (100_000 - 17) * 15);
+    assert_eq!(graph.degree(50_000), 30);
+    // 次数は高々30なのでspillせずに塗れる (spillするとslotがVMのメモリに収まらない)
+    assert!(allocate_registers2(opcodes, 32).is_ok());
+}
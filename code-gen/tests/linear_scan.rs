@@ -0,0 +1,39 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, LinearScan, RegisterAllocator};
+use compiler_practice::checker::check_allocation;
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::allocate_registers_linear_scan;
+
+// %1は最後まで生きているので、足りなくなったら%1をspillする
+const LONG_LIVED: &str = "
+loadi %1, 1
+loadi %2, 2
+loadi %3, 3
+add %4, %2, %3
+print %4
+print %1
+";
+
+#[test]
+fn spills_furthest_end() {
+    let opcodes = parse(LONG_LIVED).unwrap();
+    let allocation = LinearScan::new(AllocConfig::new(4)).allocate(&opcodes).unwrap();
+    assert_eq!(allocation.spilled.iter().cloned().collect::<Vec<_>>(), vec![1]);
+    assert_eq!(allocation.assignment.len(), 3);
+    assert_eq!(check_allocation(&opcodes, &allocation, 4), vec![]);
+
+    let result = run_vm(&allocation.code, &VmConfig::new(4)).unwrap();
+    assert_eq!(result.output, vec![5, 1]);
+}
+
+#[test]
+fn loop_values_keep_registers() {
+    let opcodes = parse(include_str!("../loop.s")).unwrap();
+    for register_num in 3..8 {
+        let code = allocate_registers_linear_scan(opcodes.clone(), register_num).unwrap();
+        let result = run_vm(&code, &VmConfig::new(register_num)).unwrap();
+        assert_eq!(result.output, vec![55, 25], "--regs {}", register_num);
+    }
+}
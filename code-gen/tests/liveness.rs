@@ -0,0 +1,83 @@
+extern crate compiler_practice;
+
+use compiler_practice::bitset::BitSet;
+use compiler_practice::liveness::Liveness;
+use compiler_practice::parser::parse;
+
+const LOOP: &str = "
+loadi %1, 0
+loadi %2, 1
+loop:
+add %1, %1, %2
+loadi %3, 5
+blt %1, %3, loop
+print %1
+";
+
+fn regs(live: &BitSet) -> Vec<usize> {
+    live.iter().collect()
+}
+
+#[test]
+fn loop_carried_values() {
+    let liveness = Liveness::new(&parse(LOOP).unwrap());
+    assert_eq!(liveness.len(), 7);
+
+    // ループの先頭では%1と%2が生きている
+    let cfg = liveness.cfg();
+    let header = (0..cfg.blocks.len()).find(|&id| cfg.blocks[id].label().is_some()).unwrap();
+    assert_eq!(liveness.block_start(header), 2);
+    assert_eq!(regs(liveness.block_live_in(header)), vec![1, 2]);
+    assert_eq!(regs(liveness.block_live_out(header)), vec![1, 2]);
+
+    assert_eq!(regs(&liveness.live_out(0)), vec![1]);
+    assert_eq!(regs(&liveness.live_in(5)), vec![1, 2, 3]);
+    assert_eq!(regs(&liveness.live_out(5)), vec![1, 2]);
+    assert!(liveness.live_out(6).is_empty());
+}
+
+#[test]
+fn queries() {
+    let liveness = Liveness::new(&parse(LOOP).unwrap());
+
+    // 書き込まれる命令では生きている
+    assert!(liveness.is_live_at(2, 1));
+    // 最後に読まれる命令では生きていない
+    assert!(!liveness.is_live_at(3, 5));
+    assert!(!liveness.is_live_at(1, 6));
+    // 後ろ向きの辺があるので%2はループ全体で生きている
+    assert!((1..6).all(|point| liveness.is_live_at(2, point)));
+
+    assert_eq!(regs(&liveness.live_at(4)), vec![1, 2, 3]);
+    assert_eq!(regs(&liveness.live_across(3)), vec![2]);
+    assert_eq!(regs(&liveness.live_across(5)), vec![1, 2]);
+}
+
+#[test]
+fn straight_line() {
+    let liveness = Liveness::new(&parse(include_str!("../parallel.s")).unwrap());
+    // loadi 6個のあと、addの前では全部生きている
+    assert_eq!(regs(&liveness.live_in(6)), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(regs(&liveness.live_at(8)), vec![7, 8, 9]);
+    assert_eq!(regs(&liveness.live_out(10)), vec![9]);
+}
+
+#[test]
+fn walk_block() {
+    let opcodes = parse(LOOP).unwrap();
+    let liveness = Liveness::new(&opcodes);
+    let cfg = liveness.cfg();
+
+    // どのブロックを辿っても、命令ごとの問い合わせと同じ集合が後ろから順に来る
+    let mut points = Vec::new();
+    for block in 0..cfg.blocks.len() {
+        let mut walked = Vec::new();
+        liveness.for_each_live_out(block, |point, live| {
+            assert_eq!(regs(live), regs(&liveness.live_out(point)), "{}", point);
+            walked.push(point);
+        });
+        walked.reverse();
+        points.extend(walked);
+    }
+    assert_eq!(points, (0..opcodes.len()).collect::<Vec<_>>());
+}
@@ -0,0 +1,53 @@
+extern crate compiler_practice;
+
+use compiler_practice::parser::{parse, ParseError, ParseErrorKind};
+
+fn error(source: &str) -> ParseError {
+    parse(source).unwrap_err()
+}
+
+#[test]
+fn unknown_mnemonic() {
+    let err = error("loadi %1, 1\n  sub %1, %1, %1\n");
+    assert_eq!(err, ParseError { line: 2, column: 3, kind: ParseErrorKind::UnknownMnemonic("sub".to_string()) });
+    assert_eq!(err.to_string(), "2:3: unknown mnemonic `sub`");
+}
+
+#[test]
+fn bad_register() {
+    for &(source, token, column) in &[("add %1, %2, 3", "3", 13), ("print %0", "%0", 7), ("load %x,1", "%x", 6)] {
+        let err = error(source);
+        assert_eq!(err, ParseError { line: 1, column, kind: ParseErrorKind::BadRegister(token.to_string()) }, "{}", source);
+        assert_eq!(err.to_string(), format!("1:{}: bad register `{}`", column, token));
+    }
+}
+
+#[test]
+fn bad_immediate() {
+    for &(source, token, column) in &[("loadi %1, 1x", "1x", 11), ("load %1, --2", "--2", 10), ("store , %1", "", 7)] {
+        let err = error(source);
+        assert_eq!(err, ParseError { line: 1, column, kind: ParseErrorKind::BadImmediate(token.to_string()) }, "{}", source);
+    }
+
+    let err = error("; comment\nloadi %1, -2147483648\nloadi %2, 2147483648\n");
+    assert_eq!(err, ParseError { line: 3, column: 11, kind: ParseErrorKind::ImmediateOverflow("2147483648".to_string()) });
+    assert_eq!(err.to_string(), "3:11: immediate `2147483648` does not fit in i32");
+}
+
+#[test]
+fn wrong_operand_count() {
+    let err = error("loadi %1, 1\n    add %1, %1\n");
+    let kind = ParseErrorKind::WrongOperandCount { mnemonic: "add".to_string(), expected: 3, found: 2 };
+    assert_eq!(err, ParseError { line: 2, column: 5, kind });
+    assert_eq!(err.to_string(), "2:5: `add` takes 3 operand(s) but 2 given");
+
+    assert_eq!(error("print").to_string(), "1:1: `print` takes 1 operand(s) but 0 given");
+}
+
+#[test]
+fn labels() {
+    assert_eq!(error("loop:\n    blt %1, %2\n").to_string(), "2:5: `blt` takes 3 operand(s) but 2 given");
+    assert_eq!(error("jmp 1x\n").kind, ParseErrorKind::BadLabel("1x".to_string()));
+    assert_eq!(error("end:\nloop:\nend:\n").to_string(), "3:1: label `end` is already defined");
+    assert_eq!(error("loadi %1, 1\njmp done\n").to_string(), "2:5: label `done` is not defined");
+}
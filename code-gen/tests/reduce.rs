@@ -0,0 +1,98 @@
+extern crate compiler_practice;
+
+use std::collections::BTreeSet;
+
+use compiler_practice::alloc::{AllocConfig, AllocError, Allocation, Chaitin, RegisterAllocator};
+use compiler_practice::fuzz::{check, reduce, repro, Failure};
+use compiler_practice::gen::{generate, GenConfig, Mix};
+use compiler_practice::parser::parse;
+use compiler_practice::printer::Listing;
+use compiler_practice::reduce::{minimize, renumber_registers};
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::OpeCode;
+
+fn output(opcodes: &[OpeCode]) -> Vec<i32> {
+    run_vm(opcodes, &VmConfig::new(16)).unwrap().output
+}
+
+// 使っているレジスタが%1から隙間なく並んでいるか
+fn dense(opcodes: &[OpeCode]) -> bool {
+    let ids: BTreeSet<usize> = opcodes.iter().flat_map(|opcode| opcode.registers()).map(|reg| reg.id).collect();
+    ids.iter().cloned().eq(1..ids.len() + 1)
+}
+
+// 1つ命令を取り除くと失敗しなくなる
+fn one_minimal<F: FnMut(&[OpeCode]) -> bool>(opcodes: &[OpeCode], mut fails: F) -> bool {
+    (0..opcodes.len()).all(|index| {
+        let mut candidate = opcodes.to_vec();
+        candidate.remove(index);
+        candidate.is_empty() || !fails(&candidate)
+    })
+}
+
+#[test]
+fn renumbers_in_order_of_appearance() {
+    let opcodes = parse("loadi %7, 1\nload %3, 4\nadd %9, %3, %7\nprint %9\n").unwrap();
+    assert_eq!(renumber_registers(&opcodes), parse("loadi %1, 1\nload %2, 4\nadd %3, %2, %1\nprint %3\n").unwrap());
+}
+
+#[test]
+fn minimize_program() {
+    let source = "
+loadi %5, 1
+loadi %9, 2
+loadi %7, 9
+add %3, %5, %9
+print %7
+print %3
+";
+    let fails = |opcodes: &[OpeCode]| output(opcodes).contains(&3);
+    let reduced = minimize(&parse(source).unwrap(), fails);
+    assert_eq!(reduced, parse("loadi %1, 1\nloadi %2, 2\nadd %3, %1, %2\nprint %3\n").unwrap());
+    assert!(one_minimal(&reduced, fails));
+}
+
+// spillした値を読み戻すLoadを落とす
+struct DropLoads(Chaitin);
+
+impl RegisterAllocator for DropLoads {
+    fn name(&self) -> &'static str {
+        "drop-loads"
+    }
+
+    fn config(&self) -> &AllocConfig {
+        self.0.config()
+    }
+
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError> {
+        let mut allocation = self.0.allocate(opcodes)?;
+        allocation.code.retain(|opcode| !matches!(*opcode, OpeCode::Load { .. }));
+        Ok(allocation)
+    }
+}
+
+#[test]
+fn reduce_fuzz_failure() {
+    let allocator = DropLoads(Chaitin::new(AllocConfig::new(3)));
+    // プログラム自身のLoadは作らない
+    let config = GenConfig { length: 40, mix: Mix { store: 0, load: 0, ..Mix::default() }, ..GenConfig::default() };
+    let failures: Vec<(Vec<OpeCode>, Failure)> = (0..100)
+        .map(|seed| generate(seed, &config))
+        .filter_map(|opcodes| check(&opcodes, &allocator).err().map(|failure| (opcodes, failure)))
+        .take(5)
+        .collect();
+    assert_eq!(failures.len(), 5);
+
+    for (opcodes, failure) in &failures {
+        let fails = |candidate: &[OpeCode]| check(candidate, &allocator).err().is_some_and(|found| found.same_kind(failure));
+        let reduced = reduce(opcodes, &allocator, failure);
+        assert!(reduced.len() < opcodes.len());
+        assert!(fails(&reduced));
+        assert!(one_minimal(&reduced, fails));
+        assert!(dense(&reduced), "{}", Listing::new(&reduced));
+
+        let source = repro(&reduced, failure);
+        assert!(source.starts_with("; algo: drop-loads\n; regs: 3\n; "), "{}", source);
+        assert_eq!(parse(&source).unwrap(), reduced);
+    }
+}
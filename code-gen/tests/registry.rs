@@ -0,0 +1,84 @@
+extern crate compiler_practice;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use compiler_practice::alloc::{AllocConfig, AllocError, AllocStats, Allocation, Naive, RegisterAllocator, Registry};
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::OpeCode;
+
+const SOURCE: &str = include_str!("../source.s");
+
+// 入力をそのまま返す
+struct Identity {
+    config: AllocConfig,
+}
+
+impl RegisterAllocator for Identity {
+    fn name(&self) -> &'static str {
+        "identity"
+    }
+
+    fn config(&self) -> &AllocConfig {
+        &self.config
+    }
+
+    fn allocate(&self, opcodes: &[OpeCode]) -> Result<Allocation, AllocError> {
+        let assignment = (1..self.config.register_num + 1).map(|id| (id, id)).collect();
+        Ok(Allocation::new(opcodes, opcodes.to_vec(), assignment, BTreeSet::new(), BTreeMap::new()))
+    }
+}
+
+#[test]
+fn default_registry() {
+    let registry = Registry::default();
+    assert_eq!(registry.names(), vec!["naive", "chaitin", "briggs", "linear-scan", "irc"]);
+    assert!(registry.create("greedy", AllocConfig::new(4)).is_none());
+
+    let opcodes = parse(SOURCE).unwrap();
+    let allocators = registry.create_all(&AllocConfig::new(4));
+    assert_eq!(allocators.iter().map(|allocator| allocator.name()).collect::<Vec<_>>(), registry.names());
+    for allocator in &allocators {
+        assert_eq!(allocator.config(), &AllocConfig::new(4));
+        let allocation = allocator.allocate(&opcodes).unwrap();
+        assert_eq!(run_vm(&allocation.code, &VmConfig::new(4)).unwrap().output, vec![10], "{}", allocator.name());
+        assert_eq!(allocation.stats.instructions_before, opcodes.len());
+        assert_eq!(allocation.stats.instructions_after, allocation.code.len());
+    }
+}
+
+#[test]
+fn allocation_result() {
+    let opcodes = parse(SOURCE).unwrap();
+    let allocation = Naive::new(AllocConfig::new(4)).allocate(&opcodes).unwrap();
+
+    // 割り当てられたレジスタは一時レジスタ (%3, %4) 以外
+    assert!(allocation.assignment.values().all(|&reg| reg < 3), "{:?}", allocation.assignment);
+    assert!(allocation.spilled.iter().all(|reg| !allocation.assignment.contains_key(reg)));
+    assert_eq!(allocation.slots.keys().cloned().collect::<BTreeSet<_>>(), allocation.spilled);
+    assert_eq!(allocation.assignment.len() + allocation.spilled.len(), 7);
+
+    let loads = allocation.code.iter().filter(|opcode| matches!(**opcode, OpeCode::Load { .. })).count();
+    let stores = allocation.code.iter().filter(|opcode| matches!(**opcode, OpeCode::Store { .. })).count();
+    assert_eq!((allocation.stats.loads, allocation.stats.stores), (loads, stores));
+}
+
+#[test]
+fn register_allocators() {
+    let mut registry = Registry::new();
+    assert!(registry.names().is_empty());
+
+    registry.register("identity", |config| Box::new(Identity { config }));
+    registry.register("naive", |config| Box::new(Naive::new(config)));
+    assert_eq!(registry.names(), vec!["identity", "naive"]);
+
+    let opcodes = parse(SOURCE).unwrap();
+    let allocation = registry.create("identity", AllocConfig::new(8)).unwrap().allocate(&opcodes).unwrap();
+    assert_eq!(allocation.code, opcodes);
+    assert_eq!(allocation.stats, AllocStats { instructions_before: 8, instructions_after: 8, loads: 0, stores: 0 });
+
+    // 同じ名前で登録すると順番はそのままで置き換わる
+    registry.register("identity", |config| Box::new(Naive::new(config)));
+    assert_eq!(registry.names(), vec!["identity", "naive"]);
+    assert_eq!(registry.create("identity", AllocConfig::new(8)).unwrap().name(), "naive");
+}
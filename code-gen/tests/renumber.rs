@@ -0,0 +1,45 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, Chaitin};
+use compiler_practice::differential::compare;
+use compiler_practice::parser::parse;
+use compiler_practice::renumber::Renumbering;
+
+const SPARSE: &str = "
+loadi %100000, 1
+loadi %7, 2
+add %1, %100000, %7
+print %1
+print %100000
+";
+
+#[test]
+fn round_trip() {
+    let opcodes = parse(SPARSE).unwrap();
+    let renumbering = Renumbering::new(&opcodes);
+    assert_eq!(renumbering.len(), 3);
+
+    let dense = renumbering.apply(&opcodes);
+    assert_eq!(dense[2].to_string(), "add %0, %2, %1");
+    assert_eq!(renumbering.restore(&dense), opcodes);
+}
+
+#[test]
+fn lookups() {
+    let renumbering = Renumbering::new(&parse(SPARSE).unwrap());
+    assert_eq!(renumbering.dense(7), Some(1));
+    assert_eq!(renumbering.dense(8), None);
+    assert_eq!(renumbering.sparse(2), 100000);
+    // 付け直したあとに増えたレジスタ
+    assert_eq!(renumbering.sparse(3), 100001);
+    assert_eq!(renumbering.sparse(5), 100003);
+}
+
+#[test]
+fn sparse_program_allocates() {
+    let opcodes = parse(SPARSE).unwrap();
+    let allocation = compare(&opcodes, &Chaitin::new(AllocConfig::new(4))).unwrap();
+    assert!(allocation.spilled.is_empty());
+    assert_eq!(allocation.assignment.keys().cloned().collect::<Vec<_>>(), vec![1, 7, 100000]);
+    assert_eq!(allocation.ranges, opcodes);
+}
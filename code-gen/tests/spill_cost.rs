@@ -0,0 +1,111 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, Allocation, Candidate, Chaitin, Coloring, Frequency, LowestNumber, RegisterAllocator, SpillHeuristic};
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, VmConfig};
+use compiler_practice::OpeCode;
+
+// ループの中で4つの値が生きている。%4はループの外でしか使わない
+const LOOP: &str = "
+loadi %1, 0
+loadi %2, 1
+loadi %3, 5
+loadi %4, 7
+loop:
+add %1, %1, %2
+blt %1, %3, loop
+print %1
+print %4
+";
+
+// rangesでloadi dst, valueのdst
+fn loaded(allocation: &Allocation, value: i32) -> usize {
+    allocation.ranges.iter().filter_map(|opcode| match *opcode {
+        OpeCode::LdI { ref dst, value: ref v } if v.value == value => Some(dst.id),
+        _ => None,
+    }).next().unwrap()
+}
+
+fn output(opcodes: &[OpeCode]) -> Vec<i32> {
+    run_vm(opcodes, &VmConfig::new(3)).unwrap().output
+}
+
+#[test]
+fn spills_values_outside_loops() {
+    let opcodes = parse(LOOP).unwrap();
+    let allocation = Chaitin::new(AllocConfig::new(3)).allocate(&opcodes).unwrap();
+    let outside = loaded(&allocation, 7);
+    let step = loaded(&allocation, 1);
+    // %4のStoreの一時レジスタがループの3つの値と干渉するので、次にループの中で読むだけの%2をspillする
+    assert_eq!(allocation.spilled.iter().cloned().collect::<Vec<_>>(), vec![step, outside]);
+    assert_eq!(output(&allocation.code), vec![5, 7]);
+
+    let decision = &allocation.spill_decisions[0];
+    assert_eq!(allocation.spill_decisions.len(), 2);
+    assert_eq!((decision.candidate.reg, decision.candidate.uses, decision.candidate.defs), (outside, 1, 1));
+    assert_eq!((decision.candidate.weight, decision.candidate.degree, decision.candidates), (2.0, 3, 4));
+    assert!(decision.spilled);
+    assert!(decision.to_string().starts_with(&format!("%{}: cost 0.667", outside)));
+    let decision = &allocation.spill_decisions[1];
+    assert_eq!((decision.candidate.reg, decision.candidate.weight, decision.candidates), (step, 11.0, 3));
+
+    // 番号だけで選ぶとループの中で使う値からspillする
+    let allocation = Chaitin::new(AllocConfig::new(3)).with_spill_heuristic(Box::new(LowestNumber)).allocate(&opcodes).unwrap();
+    assert_eq!(allocation.spill_decisions[0].candidate.reg, loaded(&allocation, 0));
+    assert!(allocation.spilled.len() > 1);
+    assert_eq!(output(&allocation.code), vec![5, 7]);
+}
+
+#[test]
+fn profiled_frequency() {
+    let opcodes = parse(LOOP).unwrap();
+    let counts = run_vm(&opcodes, &VmConfig::new(4)).unwrap().counts;
+    let allocation = Chaitin::new(AllocConfig::new(3)).with_frequency(Frequency::Profile(counts)).allocate(&opcodes).unwrap();
+    let decision = &allocation.spill_decisions[0];
+    assert_eq!(decision.candidate.reg, loaded(&allocation, 7));
+    assert_eq!(decision.candidate.weight, 2.0);
+
+    // 一度も実行しなかったことにすると重みは0
+    let allocation = Chaitin::new(AllocConfig::new(3)).with_frequency(Frequency::Profile(Vec::new())).allocate(&opcodes).unwrap();
+    assert!(allocation.spill_decisions.iter().all(|decision| decision.cost == 0.0));
+}
+
+// 書き込みの多いものからspillする
+struct MostDefs;
+
+impl SpillHeuristic for MostDefs {
+    fn name(&self) -> &'static str {
+        "most-defs"
+    }
+
+    fn cost(&self, candidate: &Candidate) -> f64 {
+        -(candidate.defs as f64)
+    }
+}
+
+#[test]
+fn custom_heuristic() {
+    let opcodes = parse(LOOP).unwrap();
+    let allocation = Chaitin::new(AllocConfig::new(3)).with_spill_heuristic(Box::new(MostDefs)).allocate(&opcodes).unwrap();
+    let decision = &allocation.spill_decisions[0];
+    assert_eq!(decision.candidate.reg, loaded(&allocation, 0));
+    assert_eq!(decision.cost, -2.0);
+    assert_eq!(output(&allocation.code), vec![5, 7]);
+}
+
+#[test]
+fn optimistic_decisions() {
+    for source in &[include_str!("../parallel.s"), include_str!("../loop.s"), LOOP] {
+        let opcodes = parse(source).unwrap();
+        for register_num in 3..8 {
+            let allocation = Chaitin::with_coloring(AllocConfig::new(register_num), Coloring::Optimistic).allocate(&opcodes).unwrap();
+            let spilled: Vec<usize> = allocation.spill_decisions.iter()
+                .filter(|decision| decision.spilled)
+                .map(|decision| decision.candidate.reg)
+                .collect();
+            assert!(spilled.iter().all(|reg| allocation.spilled.contains(reg)), "--regs {}\n{}", register_num, source);
+            assert!(allocation.spilled.iter().all(|reg| spilled.contains(reg)), "--regs {}\n{}", register_num, source);
+        }
+    }
+}
+
@@ -0,0 +1,74 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, Chaitin, Naive, RegisterAllocator, MIN_REGISTER_NUM};
+use compiler_practice::parser::parse;
+use compiler_practice::symbolic::{equivalent, evaluate, Terms};
+use compiler_practice::vm::{run_vm, VmConfig};
+
+#[test]
+fn allocators_are_equivalent() {
+    for source in &[include_str!("../source.s"), include_str!("../dest.s"), include_str!("../parallel.s")] {
+        let opcodes = parse(source).unwrap();
+        for register_num in MIN_REGISTER_NUM..10 {
+            let config = AllocConfig::new(register_num);
+            let allocators: Vec<Box<dyn RegisterAllocator>> = vec![Box::new(Naive::new(config.clone())), Box::new(Chaitin::new(config))];
+            for allocator in allocators {
+                let allocation = allocator.allocate(&opcodes).unwrap();
+                if let Err(difference) = equivalent(&opcodes, &allocation.code) {
+                    panic!("{} --regs {}: {}", allocator.name(), register_num, difference);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn add_is_commutative() {
+    let mut terms = Terms::new();
+    let a = evaluate(&parse("loadi %1, 1\nloadi %2, 2\nadd %3, %1, %2\nprint %3\n").unwrap(), &mut terms);
+    let b = evaluate(&parse("loadi %2, 1\nloadi %1, 2\nadd %3, %1, %2\nprint %3\n").unwrap(), &mut terms);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn spilled_values_round_trip() {
+    let original = parse("loadi %1, 1\nload %2, 5\nadd %3, %1, %2\nprint %3\n").unwrap();
+    let allocated = parse("loadi %1, 1\nstore 9, %1\nload %2, 5\nload %1, 9\nadd %3, %1, %2\nprint %3\n").unwrap();
+    assert_eq!(equivalent(&original, &allocated), Ok(()));
+}
+
+// 同じ定数を読む2つのレジスタを取り違えても、VMでは出力が変わらない
+#[test]
+fn finds_bugs_the_vm_misses() {
+    let original = parse("loadi %1, 5\nloadi %2, 5\nprint %1\nprint %2\n").unwrap();
+    let allocated = parse("loadi %1, 5\nloadi %1, 5\nprint %1\nprint %1\n").unwrap();
+
+    let run = |opcodes| run_vm(opcodes, &VmConfig::new(2)).unwrap().output;
+    assert_eq!(run(&original), run(&allocated));
+
+    let difference = equivalent(&original, &allocated).unwrap_err();
+    assert_eq!(difference.index, 0);
+    assert_eq!(difference.expected, Some("c0(5)".to_string()));
+    assert_eq!(difference.actual, Some("c1(5)".to_string()));
+}
+
+#[test]
+fn different_constants() {
+    let original = parse("loadi %1, 5\nprint %1\n").unwrap();
+    let allocated = parse("loadi %1, 6\nprint %1\n").unwrap();
+    let difference = equivalent(&original, &allocated).unwrap_err();
+    assert_eq!(difference.to_string(), "print #0 expected c0(5) but got c0(6)");
+
+    // 出力に使わない定数は違ってもよい
+    let original = parse("loadi %1, 5\nloadi %2, 7\nprint %2\n").unwrap();
+    let allocated = parse("loadi %1, 6\nloadi %2, 7\nprint %2\n").unwrap();
+    assert_eq!(equivalent(&original, &allocated), Ok(()));
+}
+
+#[test]
+fn reload_from_unwritten_slot() {
+    let original = parse("loadi %1, 1\nprint %1\n").unwrap();
+    let allocated = parse("loadi %1, 1\nload %1, 9\nprint %1\n").unwrap();
+    let difference = equivalent(&original, &allocated).unwrap_err();
+    assert_eq!(difference.actual, Some("mem[9]".to_string()));
+}
@@ -0,0 +1,140 @@
+extern crate compiler_practice;
+
+use compiler_practice::ir::Label;
+use compiler_practice::parser::parse;
+use compiler_practice::vm::{run_vm, run_vm_with_sink, OutputSink, Overflow, VmConfig, VmError, VmErrorKind};
+use compiler_practice::{Integer, OpeCode, Register};
+
+// %1と%2の和を出力してメモリに残す
+const SUM: &str = "
+loadi %1, 2
+loadi %2, 3
+add %3, %1, %2
+print %1
+store 5, %3
+load %1, 5
+print %1
+";
+
+fn error(opcodes: &[OpeCode], config: &VmConfig) -> VmError {
+    run_vm(opcodes, config).unwrap_err()
+}
+
+#[test]
+fn address_out_of_range() {
+    let config = VmConfig { memory_size: 4, ..VmConfig::new(2) };
+    let opcodes = parse("loadi %1, 1\nstore 3, %1\nstore 4, %1\n").unwrap();
+    let err = error(&opcodes, &config);
+    assert_eq!(err, VmError { index: 2, kind: VmErrorKind::AddressOutOfRange { addr: 4 } });
+    assert_eq!(err.to_string(), "instruction 2: address 4 is out of range");
+
+    let opcodes = parse("load %1, -1\n").unwrap();
+    assert_eq!(error(&opcodes, &config), VmError { index: 0, kind: VmErrorKind::AddressOutOfRange { addr: -1 } });
+}
+
+#[test]
+fn register_out_of_range() {
+    let opcodes = parse("loadi %1, 1\nloadi %2, 2\nadd %3, %1, %2\n").unwrap();
+    let err = error(&opcodes, &VmConfig::new(2));
+    assert_eq!(err, VmError { index: 2, kind: VmErrorKind::RegisterOutOfRange { reg: 3 } });
+    assert_eq!(err.to_string(), "instruction 2: register %3 is out of range");
+
+    // 読むレジスタも調べる
+    let opcodes = parse("loadi %1, 1\nprint %1\nprint %4\n").unwrap();
+    assert_eq!(error(&opcodes, &VmConfig::new(3)), VmError { index: 2, kind: VmErrorKind::RegisterOutOfRange { reg: 4 } });
+}
+
+const OVERFLOW: &str = "
+loadi %1, 2147483647
+loadi %2, 1
+print %1
+add %1, %1, %2
+print %1
+";
+
+#[test]
+fn overflow() {
+    let opcodes = parse(OVERFLOW).unwrap();
+    let config = |overflow| VmConfig { overflow, ..VmConfig::new(2) };
+
+    assert_eq!(run_vm(&opcodes, &config(Overflow::Wrapping)).unwrap().output, vec![i32::MAX, i32::MIN]);
+    assert_eq!(run_vm(&opcodes, &config(Overflow::Saturating)).unwrap().output, vec![i32::MAX, i32::MAX]);
+
+    let err = error(&opcodes, &config(Overflow::Checked));
+    assert_eq!(err, VmError { index: 3, kind: VmErrorKind::Overflow });
+    assert_eq!(err.to_string(), "instruction 3: integer overflow");
+
+    // 溢れなければCheckedでも止まらない
+    let opcodes = parse("loadi %1, -2147483648\nloadi %2, 2147483647\nadd %1, %1, %2\nprint %1\n").unwrap();
+    assert_eq!(run_vm(&opcodes, &config(Overflow::Checked)).unwrap().output, vec![-1]);
+}
+
+#[test]
+fn step_limit() {
+    let opcodes = parse("loop:\njmp loop\n").unwrap();
+    let err = error(&opcodes, &VmConfig { max_steps: 10, ..VmConfig::new(1) });
+    assert_eq!(err, VmError { index: 0, kind: VmErrorKind::StepLimit { max_steps: 10 } });
+    assert_eq!(err.to_string(), "instruction 0: did not halt within 10 steps");
+}
+
+// パーサーを通さないと未定義のラベルに飛べる
+#[test]
+fn undefined_label() {
+    let opcodes = vec![OpeCode::LdI { dst: Register::new(1), value: Integer::new(0) }, OpeCode::Jmp { target: Label::new("end") }];
+    let err = error(&opcodes, &VmConfig::new(1));
+    assert_eq!(err, VmError { index: 1, kind: VmErrorKind::UndefinedLabel { label: "end".to_string() } });
+    assert_eq!(err.to_string(), "instruction 1: label `end` is not defined");
+}
+
+#[test]
+fn execution_result() {
+    let opcodes = parse(SUM).unwrap();
+    let result = run_vm(&opcodes, &VmConfig::new(4)).unwrap();
+    assert_eq!(result.output, vec![2, 5]);
+    assert_eq!(result.registers, vec![0, 5, 3, 5, 0]);
+    assert_eq!(result.memory.len(), 1024);
+    assert_eq!(result.memory[..6], [0, 0, 0, 0, 0, 5]);
+    assert_eq!(result.counts, vec![1; opcodes.len()]);
+    assert_eq!(result.executed, opcodes.len());
+}
+
+// 受け取った値を10倍して覚えておく
+struct Scaled(Vec<i32>);
+
+impl OutputSink for Scaled {
+    fn print(&mut self, value: i32) {
+        self.0.push(value * 10);
+    }
+}
+
+#[test]
+fn output_sink() {
+    let opcodes = parse(SUM).unwrap();
+    let mut sink = Scaled(Vec::new());
+    let config = VmConfig::new(3);
+    let result = run_vm_with_sink(&opcodes, &config, &mut sink).unwrap();
+    assert_eq!(sink.0, vec![20, 50]);
+    assert_eq!(result.output, vec![2, 5]);
+
+    // 失敗するまでに出力した値は届いている
+    let mut sink: Vec<i32> = Vec::new();
+    let config = VmConfig { memory_size: 4, ..config };
+    assert!(run_vm_with_sink(&opcodes, &config, &mut sink).is_err());
+    assert_eq!(sink, vec![2]);
+}
+
+#[test]
+fn dump() {
+    let opcodes = parse(SUM).unwrap();
+    let result = run_vm(&opcodes, &VmConfig::new(3)).unwrap();
+    assert_eq!(result.dump().to_string(), "registers\n  %1 = 5\n  %2 = 3\n  %3 = 5\nmemory\n  5: 5\n");
+}
+
+#[test]
+fn loop_counts() {
+    let opcodes = parse("loadi %1, 0\nloadi %2, 1\nloadi %3, 3\nloop:\nadd %1, %1, %2\nblt %1, %3, loop\nprint %1\n").unwrap();
+    let result = run_vm(&opcodes, &VmConfig::new(3)).unwrap();
+    assert_eq!(result.output, vec![3]);
+    assert_eq!(result.counts, vec![1, 1, 1, 3, 3, 3, 1]);
+    assert_eq!(result.executed, result.counts.iter().sum::<usize>());
+}
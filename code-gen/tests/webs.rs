@@ -0,0 +1,99 @@
+extern crate compiler_practice;
+
+use compiler_practice::alloc::{AllocConfig, Chaitin};
+use compiler_practice::differential::compare;
+use compiler_practice::parser::parse;
+use compiler_practice::webs::split_webs;
+use compiler_practice::{Integer, OpeCode, Register};
+
+fn split(source: &str) -> String {
+    split_webs(&parse(source).unwrap()).code.iter().map(|op| format!("{}\n", op)).collect()
+}
+
+#[test]
+fn accumulating_into_the_same_register() {
+    let webs = split_webs(&parse(include_str!("../dest.s")).unwrap());
+    assert_eq!(webs.code[5].to_string(), "add %6, %5, %3");
+    assert_eq!(webs.code[6].to_string(), "add %7, %6, %4");
+    assert_eq!(webs.code[7].to_string(), "print %7");
+    assert_eq!(webs.original.get(&6), Some(&5));
+    assert_eq!(webs.original.get(&7), Some(&5));
+}
+
+#[test]
+fn single_definitions_are_unchanged() {
+    for source in &[include_str!("../source.s"), include_str!("../parallel.s")] {
+        let opcodes = parse(source).unwrap();
+        assert_eq!(split_webs(&opcodes).code, opcodes);
+    }
+}
+
+// 両方の定義が同じ使用に届くので1つのwebになる
+#[test]
+fn definitions_meeting_at_a_join() {
+    let source = "
+loadi %1, 1
+loadi %2, 2
+beq %1, %2, else
+loadi %3, 10
+jmp join
+else:
+loadi %3, 20
+join:
+print %3
+loadi %3, 30
+print %3
+";
+    let expected = "
+loadi %1, 1
+loadi %2, 2
+beq %1, %2, else
+loadi %3, 10
+jmp join
+else:
+loadi %3, 20
+join:
+print %3
+loadi %4, 30
+print %4
+";
+    assert_eq!(split(source), split(expected));
+
+    // ループを回る値も1つのweb
+    let opcodes = parse(include_str!("../loop.s")).unwrap();
+    assert_eq!(split_webs(&opcodes).code, opcodes);
+}
+
+// %1を1つの生存区間として扱うと%1, %2, %3が互いに干渉し、2色では塗れない
+#[test]
+fn webs_avoid_spills() {
+    let opcodes = parse("
+loadi %1, 1
+loadi %2, 2
+print %1
+loadi %3, 3
+print %2
+loadi %1, 4
+print %3
+print %1
+").unwrap();
+    let allocation = compare(&opcodes, &Chaitin::new(AllocConfig::new(4))).unwrap();
+    assert!(allocation.spilled.is_empty());
+    assert_eq!(allocation.stats.stores, 0);
+    assert_eq!(allocation.ranges[5].to_string(), "loadi %4, 4");
+}
+
+// 同じレジスタへの書き込みが多くても、書き込みごとに別のwebになる
+// (書き込みの数の2乗の時間がかかる実装だとこのテストは終わらない)
+#[test]
+fn many_redefinitions() {
+    let mut opcodes = Vec::new();
+    for i in 0..50_000 {
+        opcodes.push(OpeCode::LdI { dst: Register::new(1), value: Integer::new(i) });
+        opcodes.push(OpeCode::Print { src: Register::new(1) });
+    }
+
+    let webs = split_webs(&opcodes);
+    assert_eq!(webs.original.len(), 50_000);
+    assert_eq!(webs.code[99_999].to_string(), "print %50000");
+}